@@ -75,6 +75,60 @@ func Handle(w http.ResponseWriter, r *http.Request) {
 						}
 						w.WriteHeader(http.StatusOK)
 						w.Write(pageBuffer.Bytes())
+					} else if pageContent.Enum == solana.PageBlobPage {
+						blobAccount, err := client.BlobAccount(pageContent.BlobPage.Hash)
+						if err != nil {
+							w.WriteHeader(http.StatusInternalServerError)
+							w.Write([]byte(err.Error()))
+							return
+						}
+						blobData, err := client.LoadAccountContent(&blobAccount)
+						if err != nil {
+							w.WriteHeader(http.StatusInternalServerError)
+							w.Write([]byte(err.Error()))
+							return
+						}
+						blobContent, err := solana.ParseBlobContent(blobData)
+						if err != nil {
+							w.WriteHeader(http.StatusInternalServerError)
+							w.Write([]byte(err.Error()))
+							return
+						}
+						segments := strings.Split(r.RequestURI, ".")
+						if len(segments) > 1 {
+							fileExtension := segments[len(segments)-1]
+							w.Header().Set("Content-Type", mime.TypeByExtension("."+fileExtension))
+						}
+						w.Header().Set("W3-Solana-Blob", blobAccount.ToBase58())
+						w.WriteHeader(http.StatusOK)
+						w.Write(blobContent)
+					} else if pageContent.Enum == solana.PageAccount {
+						accountPage := pageContent.AccountPage
+						accountData, err := client.LoadAccountContent(&accountPage.Account)
+						if err != nil {
+							w.WriteHeader(http.StatusInternalServerError)
+							w.Write([]byte(err.Error()))
+							return
+						}
+						body, err := solana.DecodeAccountPage(accountPage.Decoder, accountData)
+						if err != nil {
+							w.WriteHeader(http.StatusInternalServerError)
+							w.Write([]byte(err.Error()))
+							return
+						}
+						if accountPage.Decoder.Enum == solana.DecoderBorshJson {
+							w.Header().Set("Content-Type", "application/json")
+						} else {
+							segments := strings.Split(r.RequestURI, ".")
+							if len(segments) > 1 {
+								fileExtension := segments[len(segments)-1]
+								w.Header().Set("Content-Type", mime.TypeByExtension("."+fileExtension))
+							}
+						}
+						w.Header().Set("Cache-Control", "no-cache")
+						w.Header().Set("W3-Solana-Source", accountPage.Account.ToBase58())
+						w.WriteHeader(http.StatusOK)
+						w.Write(body)
 					}
 				} else {
 					w.WriteHeader(http.StatusInternalServerError)
@@ -0,0 +1,227 @@
+package solana
+
+import (
+	"bytes"
+	"encoding/binary"
+	"encoding/hex"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"math/big"
+	"strconv"
+	"strings"
+
+	"github.com/blocto/solana-go-sdk/common"
+)
+
+// DecodeAccountPage renders the data of the account behind an AccountPage,
+// it mirrors the schema decoder of the rust uploader.
+func DecodeAccountPage(decoder AccountDecoderEnum, data []byte) ([]byte, error) {
+	switch decoder.Enum {
+	case DecoderRaw:
+		return data, nil
+	case DecoderByteRange:
+		start := int(decoder.ByteRange.Offset)
+		end := start + int(decoder.ByteRange.Length)
+		if end > len(data) {
+			return nil, fmt.Errorf("range %d..%d out of %d bytes", start, end, len(data))
+		}
+		return data[start:end], nil
+	case DecoderBorshJson:
+		return BorshToJson(decoder.BorshJson.Schema, data)
+	default:
+		return nil, errors.New("unknown account decoder")
+	}
+}
+
+type schemaField struct {
+	Name string
+	Type string
+}
+
+func parseSchema(schema string) ([]schemaField, error) {
+	fields := []schemaField{}
+	for _, field := range strings.Split(schema, ",") {
+		field = strings.TrimSpace(field)
+		if field == "" {
+			continue
+		}
+		parts := strings.SplitN(field, ":", 2)
+		if len(parts) != 2 {
+			return nil, fmt.Errorf("field without type: %s", field)
+		}
+		fields = append(fields, schemaField{
+			Name: strings.TrimSpace(parts[0]),
+			Type: strings.TrimSpace(parts[1]),
+		})
+	}
+	return fields, nil
+}
+
+type borshReader struct {
+	data []byte
+}
+
+func (r *borshReader) take(n int) ([]byte, error) {
+	if len(r.data) < n {
+		return nil, errors.New("account data too short for schema")
+	}
+	head := r.data[:n]
+	r.data = r.data[n:]
+	return head, nil
+}
+
+func (r *borshReader) takeLen() (int, error) {
+	b, err := r.take(4)
+	if err != nil {
+		return 0, err
+	}
+	return int(binary.LittleEndian.Uint32(b)), nil
+}
+
+func littleEndianBig(b []byte, signed bool) string {
+	be := make([]byte, len(b))
+	for i := range b {
+		be[len(b)-1-i] = b[i]
+	}
+	n := new(big.Int).SetBytes(be)
+	if signed && be[0]&0x80 != 0 {
+		n.Sub(n, new(big.Int).Lsh(big.NewInt(1), uint(len(b)*8)))
+	}
+	return n.String()
+}
+
+func (r *borshReader) read(fieldType string) (interface{}, error) {
+	if strings.HasPrefix(fieldType, "vec<") && strings.HasSuffix(fieldType, ">") {
+		n, err := r.takeLen()
+		if err != nil {
+			return nil, err
+		}
+		items := make([]interface{}, 0, n)
+		for i := 0; i < n; i++ {
+			item, err := r.read(strings.TrimSpace(fieldType[4 : len(fieldType)-1]))
+			if err != nil {
+				return nil, err
+			}
+			items = append(items, item)
+		}
+		return items, nil
+	}
+	if strings.HasPrefix(fieldType, "option<") && strings.HasSuffix(fieldType, ">") {
+		tag, err := r.take(1)
+		if err != nil {
+			return nil, err
+		}
+		if tag[0] == 0 {
+			return nil, nil
+		}
+		return r.read(strings.TrimSpace(fieldType[7 : len(fieldType)-1]))
+	}
+	if strings.HasPrefix(fieldType, "[u8;") && strings.HasSuffix(fieldType, "]") {
+		n, err := strconv.Atoi(strings.TrimSpace(fieldType[4 : len(fieldType)-1]))
+		if err != nil {
+			return nil, err
+		}
+		b, err := r.take(n)
+		if err != nil {
+			return nil, err
+		}
+		return hex.EncodeToString(b), nil
+	}
+
+	sizes := map[string]int{
+		"bool": 1, "u8": 1, "i8": 1, "u16": 2, "i16": 2, "u32": 4, "i32": 4,
+		"u64": 8, "i64": 8, "u128": 16, "i128": 16, "pubkey": 32,
+	}
+	if size, ok := sizes[fieldType]; ok {
+		b, err := r.take(size)
+		if err != nil {
+			return nil, err
+		}
+		switch fieldType {
+		case "bool":
+			return b[0] != 0, nil
+		case "u8":
+			return b[0], nil
+		case "i8":
+			return int8(b[0]), nil
+		case "u16":
+			return binary.LittleEndian.Uint16(b), nil
+		case "i16":
+			return int16(binary.LittleEndian.Uint16(b)), nil
+		case "u32":
+			return binary.LittleEndian.Uint32(b), nil
+		case "i32":
+			return int32(binary.LittleEndian.Uint32(b)), nil
+		case "u64":
+			return binary.LittleEndian.Uint64(b), nil
+		case "i64":
+			return int64(binary.LittleEndian.Uint64(b)), nil
+		case "u128":
+			return littleEndianBig(b, false), nil
+		case "i128":
+			return littleEndianBig(b, true), nil
+		case "pubkey":
+			return common.PublicKeyFromBytes(b).ToBase58(), nil
+		}
+	}
+
+	switch fieldType {
+	case "string":
+		n, err := r.takeLen()
+		if err != nil {
+			return nil, err
+		}
+		b, err := r.take(n)
+		if err != nil {
+			return nil, err
+		}
+		return string(b), nil
+	case "bytes":
+		n, err := r.takeLen()
+		if err != nil {
+			return nil, err
+		}
+		b, err := r.take(n)
+		if err != nil {
+			return nil, err
+		}
+		return hex.EncodeToString(b), nil
+	}
+	return nil, fmt.Errorf("unknown type: %s", fieldType)
+}
+
+// BorshToJson renders borsh data as a JSON object keeping the schema field
+// order, fields named with a leading "_" are skipped.
+func BorshToJson(schema string, data []byte) ([]byte, error) {
+	fields, err := parseSchema(schema)
+	if err != nil {
+		return nil, err
+	}
+	reader := &borshReader{data: data}
+	buffer := bytes.NewBufferString("{")
+	first := true
+	for _, field := range fields {
+		value, err := reader.read(field.Type)
+		if err != nil {
+			return nil, err
+		}
+		if strings.HasPrefix(field.Name, "_") {
+			continue
+		}
+		name, _ := json.Marshal(field.Name)
+		encoded, err := json.Marshal(value)
+		if err != nil {
+			return nil, err
+		}
+		if !first {
+			buffer.WriteString(",")
+		}
+		first = false
+		buffer.Write(name)
+		buffer.WriteString(":")
+		buffer.Write(encoded)
+	}
+	buffer.WriteString("}")
+	return buffer.Bytes(), nil
+}
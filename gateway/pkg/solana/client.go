@@ -94,6 +94,12 @@ func (s *W3Site) UrlAccount(path string) (common.PublicKey, error) {
 	return k, err
 }
 
+func (s *W3Site) BlobAccount(hash [32]byte) (common.PublicKey, error) {
+	seeds := [][]byte{[]byte(".w3-solana-blob"), hash[:]}
+	k, _, err := common.FindProgramAddress(seeds, s.Program)
+	return k, err
+}
+
 // let base_seed = ".w3-solana-name";
 // let (config_pda, bump_seed) = PdaHelper::new(program_id.clone())
 // .find_program_address(&[base_seed.as_bytes(), name.as_bytes()]);
@@ -1,22 +1,105 @@
 package solana
 
-import "github.com/near/borsh-go"
+import (
+	"encoding/binary"
+	"errors"
+	"fmt"
+
+	"github.com/blocto/solana-go-sdk/common"
+	"github.com/near/borsh-go"
+)
 
 type PageDataEnum struct {
-	Enum      borsh.Enum `borsh_enum:"true"`
-	RawData   Raw
-	TrunkPage TrunkPage
+	Enum        borsh.Enum `borsh_enum:"true"`
+	RawData     Raw
+	TrunkPage   TrunkPage
+	BlobPage    BlobPage
+	AccountPage AccountPage
 }
 
 type Raw []uint8
 type TrunkPage struct {
 	Trunks uint8
 }
+type BlobPage struct {
+	Hash [32]uint8
+}
+type AccountPage struct {
+	Account common.PublicKey
+	Decoder AccountDecoderEnum
+}
+
+type AccountDecoderEnum struct {
+	Enum      borsh.Enum `borsh_enum:"true"`
+	Raw       struct{}
+	BorshJson BorshJsonDecoder
+	ByteRange ByteRangeDecoder
+}
+type BorshJsonDecoder struct {
+	Schema string
+}
+type ByteRangeDecoder struct {
+	Offset uint32
+	Length uint32
+}
+
+const (
+	DecoderRaw       borsh.Enum = 0
+	DecoderBorshJson borsh.Enum = 1
+	DecoderByteRange borsh.Enum = 2
+)
+
+const (
+	PageRawData   borsh.Enum = 0
+	PageTrunkPage borsh.Enum = 1
+	PageBlobPage  borsh.Enum = 2
+	PageAccount   borsh.Enum = 3
+)
+
+// blob account header: hash(32) creator(32) ref_count(8) size(4) sealed(1)
+const blobHeaderLen = 32 + 32 + 8 + 4 + 1
+
+func ParseBlobContent(data []byte) ([]byte, error) {
+	if len(data) < blobHeaderLen {
+		return nil, errors.New("blob account too small")
+	}
+	size := binary.LittleEndian.Uint32(data[72:76])
+	if data[76] == 0 {
+		return nil, errors.New("blob not sealed")
+	}
+	if int(size) > len(data)-blobHeaderLen {
+		return nil, errors.New("blob size out of range")
+	}
+	return data[blobHeaderLen : blobHeaderLen+int(size)], nil
+}
+
+// pageAuthorityLen is the size of the authority key the program stores after
+// the page data. Pages written before authorities were recorded lack it.
+const pageAuthorityLen = 32
 
+// ParsePageContent decodes a page account, the borsh page data optionally
+// followed by the key of its authority.
 func ParsePageContent(data []byte) (*PageDataEnum, error) {
+	if len(data) > pageAuthorityLen {
+		if page, err := parsePageData(data[:len(data)-pageAuthorityLen]); err == nil {
+			return page, nil
+		}
+	}
+	return parsePageData(data)
+}
+
+// parsePageData decodes page data filling the whole of data.
+func parsePageData(data []byte) (*PageDataEnum, error) {
 	b := new(PageDataEnum)
 	if err := borsh.Deserialize(b, data); err != nil {
 		return nil, err
 	}
+	encoded, err := borsh.Serialize(*b)
+	if err != nil {
+		return nil, err
+	}
+	if len(encoded) != len(data) {
+		return nil, fmt.Errorf("page data of %d bytes followed by %d unknown bytes", len(encoded), len(data)-len(encoded))
+	}
 	return b, nil
 }
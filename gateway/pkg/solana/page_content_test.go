@@ -0,0 +1,35 @@
+package solana
+
+import (
+	"bytes"
+	"testing"
+)
+
+func TestParsePageContentWithAuthority(t *testing.T) {
+	// RawData "hi"
+	page := []byte{0, 2, 0, 0, 0, 'h', 'i'}
+	authority := bytes.Repeat([]byte{7}, pageAuthorityLen)
+
+	for _, data := range [][]byte{page, append(append([]byte{}, page...), authority...)} {
+		d, err := ParsePageContent(data)
+		if err != nil {
+			t.Fatalf("parse %d bytes: %v", len(data), err)
+		}
+		if d.Enum != PageRawData || string(d.RawData) != "hi" {
+			t.Fatalf("parse %d bytes: got %+v", len(data), d)
+		}
+	}
+
+	// TrunkPage of 3 trunks, shorter than an authority
+	d, err := ParsePageContent(append([]byte{1, 3}, authority...))
+	if err != nil {
+		t.Fatal(err)
+	}
+	if d.Enum != PageTrunkPage || d.TrunkPage.Trunks != 3 {
+		t.Fatalf("got %+v", d)
+	}
+
+	if _, err := ParsePageContent(append(append([]byte{}, page...), 1, 2, 3)); err == nil {
+		t.Fatal("accepted page data followed by unknown bytes")
+	}
+}
@@ -1,5 +1,11 @@
 use solana_program::pubkey::Pubkey;
 
+/// Seed prefix of the account holding a `NameConfig`.
+pub const NAME_SEED: &str = ".w3-solana-name";
+
+/// Seed prefix of content-addressed blob accounts.
+pub const BLOB_SEED: &str = ".w3-solana-blob";
+
 pub struct PdaHelper {
     pub program: Pubkey,
 }
@@ -9,7 +15,7 @@ impl PdaHelper {
         Self { program }
     }
 
-    fn get_seeds(seed_text: &str) -> Vec<&[u8]> {
+    pub fn get_seeds(seed_text: &str) -> Vec<&[u8]> {
         let mut seeds: Vec<&[u8]> = Vec::new();
         if seed_text.len() > 32 {
             for chunk in seed_text.as_bytes().chunks(32) {
@@ -39,4 +45,16 @@ impl PdaHelper {
     pub fn find_program_address(&self, seeds: &[&[u8]]) -> (Pubkey, u8) {
         Pubkey::find_program_address(seeds, &self.program)
     }
+
+    /// Name configs always live under the program that registered the name,
+    /// the content they point at lives under `NameConfig.program`.
+    pub fn find_name_address(&self, name: &str) -> (Pubkey, u8) {
+        self.find_program_address(&[NAME_SEED.as_bytes(), name.as_bytes()])
+    }
+
+    /// Blobs are keyed by the sha256 of their content, so identical files
+    /// uploaded under different paths share one account.
+    pub fn find_blob_address(&self, hash: &[u8; 32]) -> (Pubkey, u8) {
+        self.find_program_address(&[BLOB_SEED.as_bytes(), hash])
+    }
 }
@@ -1,84 +1,693 @@
 use {
-    clap::{CommandFactory, Parser},
+    clap::{Args, CommandFactory, Parser, Subcommand},
+    clap_complete::Shell,
     env_logger::Env,
     log::{self},
-    w3_uploader::{client, sdk::ClientInfo},
+    solana_sdk::{
+        pubkey::Pubkey,
+        signature::{write_keypair_file, Keypair, Signer},
+    },
+    std::{
+        collections::BTreeMap,
+        fs,
+        io::{self, Write},
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+    w3_uploader::{
+        client,
+        config::{ConfigLayer, W3Config},
+        error::{W3ClientError, W3Result},
+        fees::PriorityFee,
+        keystore::{
+            new_passphrase, unlock_passphrase, Keystore, PassphraseSource, ScryptParams,
+            NEW_PASSPHRASE_ENV, PASSPHRASE_ENV,
+        },
+        sdk::AccountDecoder,
+        signer::{expand_home, keypair_from_source},
+    },
 };
 
 /// Upload file content to the Solana blockchain.
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
-    /// Private key (base58 format) needed to sign the transaction
-    #[arg(short, long,default_value_t = String::from(""))]
-    key: String,
+#[command(name = "w3-uploader", version, about, long_about = None)]
+struct Cli {
+    #[command(flatten)]
+    client: ClientArgs,
 
-    /// RPC URL to connect to the Solana blockchain.
-    #[arg(short, long,default_value_t = String::from("local"))]
-    network: String,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+/// Connection and signing options shared by every command, each overrides
+/// the config files and environment.
+#[derive(Args, Debug)]
+struct ClientArgs {
+    /// Project config to use instead of the nearest `w3-uploader.yaml`.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Profile of the config files to apply, defaults to $W3_PROFILE.
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Signer of the content authority: a keypair or keystore file,
+    /// `external:COMMAND` or a base58 private key. Defaults to $W3_KEYPAIR,
+    /// then the Solana CLI keypair.
+    #[arg(short, long, global = true)]
+    key: Option<String>,
+
+    /// Signer paying the transaction fees, in the same forms as `--key`.
+    /// Defaults to $W3_PAYER, then the content authority.
+    #[arg(long, global = true)]
+    payer: Option<String>,
+
+    /// Cluster to connect to: localnet, devnet, testnet, mainnet or an RPC URL.
+    #[arg(short, long, global = true)]
+    network: Option<String>,
 
     /// Program module to manage all data files.
-    #[arg(short, long,default_value_t = String::from("9pW59BsNCqtQC1xucwTXYS4Qe9qz5AgSy2jajE63odQb"))]
-    program: String,
+    #[arg(short, long, global = true)]
+    program: Option<String>,
+
+    /// Number of upload transactions awaiting confirmation at the same time.
+    #[arg(long, global = true)]
+    in_flight: Option<usize>,
+
+    /// Times a failed transaction is sent again before giving up.
+    #[arg(long, global = true)]
+    retries: Option<usize>,
+
+    /// Compute-unit price of each transaction in micro-lamports, `auto` to
+    /// estimate it from recent fees or `none`.
+    #[arg(long, global = true)]
+    priority_fee: Option<PriorityFee>,
+
+    /// Most lamports spent on priority fees by this run.
+    #[arg(long, global = true)]
+    max_priority_spend: Option<u64>,
+
+    /// Address lookup table used to fit more files in each batch.
+    #[arg(long, global = true)]
+    lookup_table: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Upload every file of a directory.
+    Upload(UploadArgs),
+    /// Upload the files changed since the last sync.
+    Sync {
+        #[command(flatten)]
+        upload: UploadArgs,
+
+        /// Delete pages of files removed locally.
+        #[arg(long)]
+        prune: bool,
+    },
+    /// Sync a directory again whenever its files change.
+    Watch {
+        /// Directory to watch, defaults to the configured `dir`.
+        dir: Option<PathBuf>,
 
-    /// Action to perform on the program module.
-    #[arg(short, long,default_value_t = String::from("upload"))]
-    action: String,
+        /// Delete pages of files removed locally.
+        #[arg(long)]
+        prune: bool,
 
-    /// Directory to upload files from.
-    #[arg(short, long ,default_value_t = String::from("."))]
-    dir: String,
+        /// Milliseconds the files must stay unchanged before deploying.
+        #[arg(long, default_value_t = 500)]
+        debounce: u64,
+    },
+    /// Print the content served at a web path.
+    Get {
+        /// Web path of the page, e.g. `/index.html`.
+        path: String,
 
-    /// Name data to config.
-    #[arg(short, long,default_value_t = String::from("w3sol"))]
-    name: String,
+        /// File to write instead of stdout.
+        #[arg(long, short)]
+        out: Option<PathBuf>,
+    },
+    /// Download a site into a local directory.
+    Pull {
+        /// Directory receiving the files.
+        dir: PathBuf,
+
+        /// Web path to download, every file of the site manifest when omitted.
+        #[arg(long = "path")]
+        paths: Vec<String>,
+    },
+    /// Compare a local directory with the content served on chain.
+    Verify {
+        /// Directory to compare, defaults to the configured `dir`.
+        dir: Option<PathBuf>,
+
+        /// Print the report as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// List the pages of the site with the accounts and rent holding them.
+    Ls {
+        /// Also list every other account of the program, e.g. names and
+        /// orphaned trunks.
+        #[arg(long)]
+        all: bool,
+
+        /// Show when each entry was last modified, one RPC request per
+        /// entry.
+        #[arg(long)]
+        modified: bool,
+
+        /// Print the listing as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Delete pages and return their rent.
+    Rm {
+        /// Web paths of the pages, e.g. `/index.html`.
+        #[arg(required = true)]
+        paths: Vec<String>,
+    },
+    /// Hand pages written before pages recorded their authority to an
+    /// authority, signed by the upgrade authority of the program.
+    Claim {
+        /// Web paths of the pages, e.g. `/index.html`.
+        #[arg(required = true)]
+        paths: Vec<String>,
+
+        /// Key allowed to rewrite and delete the pages from now on.
+        #[arg(long, value_parser = parse_pubkey)]
+        authority: Pubkey,
+    },
+    /// Serve the live data of an account at a web path.
+    AccountPage {
+        /// Account served by the page.
+        #[arg(value_parser = parse_pubkey)]
+        account: Pubkey,
+
+        /// Web path of the page.
+        #[arg(long, default_value_t = String::from("/state.json"))]
+        path: String,
+
+        /// Decoder of the page: raw, range:OFFSET:LENGTH or borsh:SCHEMA.
+        #[arg(long, default_value = "raw", value_parser = parse_decoder)]
+        decoder: AccountDecoder,
+    },
+    /// Register and manage names.
+    #[command(subcommand)]
+    Name(NameCommands),
+    /// Manage the address lookup table of the site.
+    #[command(subcommand)]
+    LookupTable(LookupTableCommands),
+    /// Encrypted keystores of signer keys.
+    #[command(subcommand)]
+    Keystore(KeystoreCommands),
+    /// Inspect the configuration.
+    #[command(subcommand)]
+    Config(ConfigCommands),
+    /// Deploy the program.
+    Deploy,
+    /// Print the completion script of a shell.
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args, Debug)]
+struct UploadArgs {
+    /// Directory to upload files from, defaults to the configured `dir`.
+    dir: Option<PathBuf>,
+
+    /// Name whose program the content is checked against, defaults to the
+    /// configured `name`.
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Store files as content-addressed blobs, skipping content already on chain.
+    #[arg(long)]
+    dedup: bool,
+
+    /// Upload only the files matching one of these globs, replaces the
+    /// configured `include`.
+    #[arg(long)]
+    include: Vec<String>,
+
+    /// Never upload the files matching these globs, replaces the configured
+    /// `exclude`.
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// Upload the files matching this glob even though they look like they
+    /// hold secrets, replaces the configured `allow_secrets`.
+    #[arg(long = "allow-secret")]
+    allow_secrets: Vec<String>,
+
+    /// Print the cost of the upload instead of sending it.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Print the upload plan as JSON.
+    #[arg(long, requires = "dry_run")]
+    json: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum NameCommands {
+    /// Register a name resolving to the current program.
+    Register {
+        /// Name to register, defaults to the configured `name`.
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Page served for the bare name.
+        #[arg(long, default_value_t = String::from("/index.html"))]
+        default_page: String,
+    },
+    /// Point a name you own at another program or default page.
+    Update {
+        /// Name to update, defaults to the configured `name`.
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Program serving the name, the current program when omitted.
+        #[arg(long, value_parser = parse_pubkey)]
+        target: Option<Pubkey>,
+
+        /// Page served for the bare name, unchanged when omitted.
+        #[arg(long)]
+        default_page: Option<String>,
+    },
+    /// Hand a name you own over to another owner.
+    Transfer {
+        /// Name to transfer, defaults to the configured `name`.
+        #[arg(long)]
+        name: Option<String>,
+
+        /// New owner of the name.
+        #[arg(value_parser = parse_pubkey)]
+        owner: Pubkey,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum LookupTableCommands {
+    /// Create a lookup table holding the accounts written by batches.
+    Create {
+        /// Directory whose accounts are added, defaults to the configured `dir`.
+        dir: Option<PathBuf>,
+    },
+    /// Print the addresses of the configured lookup table.
+    Show,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    /// Print the effective settings and the layer each comes from.
+    Show,
+}
+
+#[derive(Subcommand, Debug)]
+enum KeystoreCommands {
+    /// Encrypt the key given by `--key` into a new keystore.
+    Import {
+        #[arg(default_value = "./w3-signer.keystore")]
+        keystore: PathBuf,
+    },
+    /// Decrypt a keystore, printing the base58 key unless `--out` is given.
+    Export {
+        #[arg(default_value = "./w3-signer.keystore")]
+        keystore: PathBuf,
+
+        /// Solana CLI keypair file to write.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Change the passphrase of a keystore, or its key with `--new-key`.
+    Rotate {
+        #[arg(default_value = "./w3-signer.keystore")]
+        keystore: PathBuf,
+
+        /// Generate a new keypair, the previous keystore is kept aside. Pages,
+        /// names and blobs stay owned by the previous key.
+        #[arg(long)]
+        new_key: bool,
+    },
+}
+
+fn parse_pubkey(value: &str) -> Result<Pubkey, String> {
+    Pubkey::from_str(value).map_err(|e| e.to_string())
+}
+
+fn parse_decoder(value: &str) -> Result<AccountDecoder, String> {
+    AccountDecoder::from_string(value).map_err(|e| e.to_string())
 }
 
 fn main() {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
-    let args = Args::parse();
-    let myclient = {
-        let info = ClientInfo::load();
-        if info.loaded {
-            match info.get_w3_client() {
-                Ok(client) => client,
-                Err(_) => client::W3Client::from_args(
-                    args.program.clone(),
-                    args.network.clone(),
-                    args.key.clone(),
-                ),
-            }
-        } else {
-            client::W3Client::from_args(
-                args.program.clone(),
-                args.network.clone(),
-                args.key.clone(),
-            )
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        log::error!("Error: {}", e);
+        std::process::exit(1)
+    }
+}
+
+fn run(cli: Cli) -> W3Result<()> {
+    // these commands never talk to the cluster
+    match cli.command {
+        Commands::Completions { shell } => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+            return Ok(());
         }
+        Commands::Keystore(command) => return keystore(command, &cli.client),
+        _ => {}
+    }
+
+    let config = load_config(&cli.client, &cli.command)?;
+    if let Commands::Config(ConfigCommands::Show) = cli.command {
+        config.print();
+        return Ok(());
+    }
+    let mut myclient = match cli.command {
+        Commands::Get { .. }
+        | Commands::Pull { .. }
+        | Commands::Verify { .. }
+        | Commands::Ls { .. }
+        | Commands::LookupTable(LookupTableCommands::Show) => config.read_only_client()?,
+        _ => config.client()?,
     };
-    match args.action.as_str() {
-        "upload" => {
-            myclient.say_hi();
-            match myclient.visit_dirs(args.dir.as_ref(), args.dir.as_ref()) {
-                Ok(_) => {
-                    log::info!("Upload completed successfully.");
-                }
-                Err(e) => {
-                    log::error!("Error: {:?}", e);
+    if let Commands::Get { .. }
+    | Commands::Pull { .. }
+    | Commands::Verify { .. }
+    | Commands::Ls { .. } = cli.command
+    {
+        myclient.read_name(&config.name.value)?;
+    }
+    let dir = &config.dir.value;
+    match cli.command {
+        Commands::Upload(args) if args.dry_run => plan(&myclient, dir, args.json)?,
+        Commands::Upload(_) => {
+            myclient.say_hi()?;
+            myclient.check_name_program(&config.name.value)?;
+            myclient.use_lookup_table(dir)?;
+            let report = myclient.visit_dirs(dir, dir)?;
+            if !report.failed.is_empty() {
+                log::error!(
+                    "Upload failed for {} files, run again to resume.",
+                    report.failed.len()
+                );
+                std::process::exit(1)
+            }
+            log::info!("Upload completed successfully.");
+        }
+        Commands::Sync { upload, .. } if upload.dry_run => plan(&myclient, dir, upload.json)?,
+        Commands::Sync { .. } => {
+            myclient.say_hi()?;
+            myclient.check_name_program(&config.name.value)?;
+            myclient.use_lookup_table(dir)?;
+            let summary = myclient.sync(dir, config.prune.value)?;
+            summary.print();
+            if !summary.failed.is_empty() {
+                std::process::exit(1)
+            }
+        }
+        Commands::Watch { debounce, .. } => {
+            myclient.say_hi()?;
+            myclient.check_name_program(&config.name.value)?;
+            myclient.use_lookup_table(dir)?;
+            myclient.watch(
+                dir,
+                config.prune.value,
+                std::time::Duration::from_millis(debounce),
+            )?;
+        }
+        Commands::Get { path, out } => {
+            let data = myclient
+                .get_page(&path)?
+                .ok_or_else(|| W3ClientError::NotFound(format!("page {}", path)))?;
+            match out {
+                Some(out) => fs::write(out, data)?,
+                None => io::stdout().write_all(&data)?,
+            }
+        }
+        Commands::Pull { dir, paths } => {
+            let summary = myclient.pull(&dir, &paths)?;
+            summary.print();
+            if !summary.missing.is_empty() || !summary.mismatched.is_empty() {
+                std::process::exit(1)
+            }
+        }
+        Commands::Verify { json, .. } => {
+            let report = myclient.verify(dir)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                report.print();
+            }
+            if !report.is_clean() {
+                std::process::exit(1)
+            }
+        }
+        Commands::Ls {
+            all,
+            modified,
+            json,
+        } => {
+            let listing = myclient.list(all, modified)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&listing)?);
+            } else {
+                listing.print();
+            }
+        }
+        Commands::Rm { paths } => {
+            myclient.say_hi()?;
+            let mut failed = 0;
+            let mut deleted = Vec::new();
+            for path in &paths {
+                match myclient.delete_content(path) {
+                    Ok(signature) => {
+                        log::info!("Deleted {} : {}", path, signature);
+                        deleted.push(path.clone());
+                    }
+                    Err(e) => {
+                        log::error!("Cannot delete {} : {}", path, e);
+                        failed += 1;
+                    }
                 }
             }
+            myclient.update_manifest(&BTreeMap::new(), &deleted)?;
+            if failed > 0 {
+                std::process::exit(1)
+            }
+        }
+        Commands::Claim { paths, authority } => {
+            myclient.say_hi()?;
+            for path in &paths {
+                let signature = myclient.claim_page(path, authority)?;
+                log::info!("Claimed {} for {} : {}", path, authority, signature);
+            }
+        }
+        Commands::AccountPage {
+            account,
+            path,
+            decoder,
+        } => {
+            myclient.say_hi()?;
+            myclient.put_account_page(&path, account, decoder)?;
+        }
+        Commands::Name(command) => {
+            myclient.say_hi()?;
+            name(&myclient, command, &config.name.value)?;
         }
-        "deploy" => {
-            myclient.say_hi();
+        Commands::LookupTable(command) => lookup_table(&myclient, command, dir)?,
+        Commands::Deploy => {
+            myclient.say_hi()?;
             myclient.deploy();
         }
-        "config_name" => {
-            myclient.say_hi();
-            myclient.config_name(args.name.as_str());
+        Commands::Completions { .. } | Commands::Keystore(_) | Commands::Config(_) => {
+            unreachable!()
         }
-        _ => {
-            Args::command().print_help().unwrap();
-            std::process::exit(1)
+    }
+    Ok(())
+}
+
+fn load_config(args: &ClientArgs, command: &Commands) -> W3Result<W3Config> {
+    let mut cli = ConfigLayer::default();
+    match command {
+        Commands::Upload(upload) | Commands::Sync { upload, .. } => {
+            cli.dir = upload.dir.clone();
+            cli.name = upload.name.clone();
+            cli.dedup = upload.dedup.then_some(true);
+            cli.include = (!upload.include.is_empty()).then(|| upload.include.clone());
+            cli.exclude = (!upload.exclude.is_empty()).then(|| upload.exclude.clone());
+            cli.allow_secrets =
+                (!upload.allow_secrets.is_empty()).then(|| upload.allow_secrets.clone());
+        }
+        Commands::Verify { dir, .. }
+        | Commands::LookupTable(LookupTableCommands::Create { dir }) => cli.dir = dir.clone(),
+        Commands::Watch { dir, prune, .. } => {
+            cli.dir = dir.clone();
+            cli.prune = prune.then_some(true);
         }
+        Commands::Name(
+            NameCommands::Register { name, .. }
+            | NameCommands::Update { name, .. }
+            | NameCommands::Transfer { name, .. },
+        ) => cli.name = name.clone(),
+        _ => {}
     }
+    if let Commands::Sync { prune: true, .. } = command {
+        cli.prune = Some(true);
+    }
+    let cli = ConfigLayer {
+        program: args.program.clone(),
+        network: args.network.clone(),
+        signer: args.key.clone(),
+        payer: args.payer.clone(),
+        in_flight: args.in_flight,
+        retries: args.retries,
+        priority_fee: args.priority_fee,
+        max_priority_spend: args.max_priority_spend,
+        lookup_table: args.lookup_table.clone(),
+        ..cli
+    };
+    W3Config::load(args.config.as_deref(), args.profile.as_deref(), cli)
+}
+
+fn plan(myclient: &client::W3Client, dir: &Path, json: bool) -> W3Result<()> {
+    let plan = myclient.plan_dirs(dir)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&plan)?);
+    } else {
+        plan.print();
+    }
+    Ok(())
+}
+
+/// Runs a name command on `name`, the name resolved from the flags and config.
+fn name(myclient: &client::W3Client, command: NameCommands, name: &str) -> W3Result<()> {
+    let signature = match command {
+        NameCommands::Register { default_page, .. } => myclient.config_name(name, &default_page)?,
+        NameCommands::Update {
+            target,
+            default_page,
+            ..
+        } => {
+            let config = myclient
+                .get_name_config(name)?
+                .ok_or_else(|| W3ClientError::NotFound(format!("name {}", name)))?;
+            myclient.update_name(
+                name,
+                target.unwrap_or(myclient.program),
+                default_page.as_deref().unwrap_or(&config.default_page),
+            )?
+        }
+        NameCommands::Transfer { owner, .. } => myclient.transfer_name(name, owner)?,
+    };
+    log::info!("Name transaction confirmed: {}", signature);
+    Ok(())
+}
+
+fn lookup_table(
+    myclient: &client::W3Client,
+    command: LookupTableCommands,
+    dir: &Path,
+) -> W3Result<()> {
+    match command {
+        LookupTableCommands::Create { .. } => {
+            myclient.say_hi()?;
+            let table = myclient.create_lookup_table()?;
+            let loaded = myclient.extend_lookup_table(&table, &myclient.hot_accounts(dir)?)?;
+            println!("{}", table);
+            log::info!(
+                "Lookup table holds {} accounts, add `lookup_table: {}` to the config to use it",
+                loaded.addresses.len(),
+                table
+            );
+        }
+        LookupTableCommands::Show => {
+            let table = myclient
+                .lookup_table
+                .ok_or_else(|| W3ClientError::Config("no lookup_table configured".to_string()))?;
+            for address in myclient.load_lookup_table(&table)?.addresses {
+                println!("{}", address);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn keystore(command: KeystoreCommands, args: &ClientArgs) -> W3Result<()> {
+    match command {
+        KeystoreCommands::Import { keystore } => keystore_import(&keystore, args),
+        KeystoreCommands::Export { keystore, out } => keystore_export(&keystore, out.as_deref()),
+        KeystoreCommands::Rotate { keystore, new_key } => keystore_rotate(&keystore, new_key),
+    }
+}
+
+fn keystore_import(path: &Path, args: &ClientArgs) -> W3Result<()> {
+    let key = args.key.as_deref().ok_or_else(|| {
+        W3ClientError::InvalidArgument("--key is required to import a key".into())
+    })?;
+    let path = expand_home(&path.to_string_lossy());
+    if path.exists() {
+        return Err(W3ClientError::InvalidArgument(format!(
+            "{} already exists, use `keystore rotate` to replace its key",
+            path.display()
+        )));
+    }
+    let keypair = keypair_from_source(key)?;
+    let passphrase = new_passphrase(PASSPHRASE_ENV, &PassphraseSource::Terminal)?;
+    Keystore::encrypt(&keypair, &passphrase, ScryptParams::default())?.write(&path)?;
+    log::info!("Imported {} into {}", keypair.pubkey(), path.display());
+    Ok(())
+}
+
+fn keystore_export(path: &Path, out: Option<&Path>) -> W3Result<()> {
+    let path = expand_home(&path.to_string_lossy());
+    let keypair =
+        Keystore::read(&path)?.decrypt(&unlock_passphrase(&path, &PassphraseSource::Terminal)?)?;
+    match out {
+        Some(out) => {
+            let out = expand_home(&out.to_string_lossy());
+            write_keypair_file(&keypair, &out)
+                .map_err(|e| W3ClientError::Key(format!("{}: {}", out.display(), e)))?;
+            log::info!("Exported {} to {}", keypair.pubkey(), out.display());
+        }
+        None => println!("{}", keypair.to_base58_string()),
+    }
+    Ok(())
+}
+
+fn keystore_rotate(path: &Path, new_key: bool) -> W3Result<()> {
+    let path = expand_home(&path.to_string_lossy());
+    let keystore = Keystore::read(&path)?;
+    let keypair = keystore.decrypt(&unlock_passphrase(&path, &PassphraseSource::Terminal)?)?;
+    let keypair = if new_key {
+        // keep the previous key, it still owns everything it wrote
+        let backup = backup_path(&path, &keystore.pubkey);
+        fs::copy(&path, &backup)?;
+        log::warn!(
+            "Pages, names and blobs written by {} stay owned by it, only the previous \
+             key rewrites or deletes them. It is kept in {}, transfer its names and \
+             move its funds before deleting it",
+            keystore.pubkey,
+            backup.display()
+        );
+        Keypair::new()
+    } else {
+        keypair
+    };
+    let passphrase = new_passphrase(NEW_PASSPHRASE_ENV, &PassphraseSource::Terminal)?;
+    Keystore::encrypt(&keypair, &passphrase, ScryptParams::default())?.write(&path)?;
+    log::info!("Keystore {} now holds {}", path.display(), keypair.pubkey());
+    Ok(())
+}
+
+fn backup_path(path: &Path, pubkey: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.bak", pubkey));
+    path.with_file_name(name)
 }
@@ -1,65 +1,164 @@
 use {
-    borsh::BorshSerialize,
+    borsh::{BorshDeserialize, BorshSerialize},
     log::{error, info, warn},
     solana_client::rpc_client::RpcClient,
     solana_sdk::{
-        instruction::AccountMeta,
+        address_lookup_table::AddressLookupTableAccount,
+        bpf_loader_upgradeable,
+        hash::hash,
+        instruction::{AccountMeta, Instruction},
         pubkey::Pubkey,
-        signature::{Keypair, Signer},
+        signature::{Signature, Signer},
         system_program,
     },
-    std::{fs, path::Path, str::FromStr},
+    std::{
+        collections::{BTreeMap, HashMap},
+        fs,
+        path::Path,
+        str::FromStr,
+    },
     w3solana::pda_helper::PdaHelper,
 };
 
 use crate::{
-    sdk::{InstructionData, Network},
-    solana_trait::SolanaTransaction,
+    engine::{UploadEngine, UploadJob, UploadReport},
+    error::{W3ClientError, W3Result},
+    fees::{FeePolicy, PriorityFee},
+    filter::{FileFilter, IgnoreStack, Pattern},
+    journal::{UploadJournal, JOURNAL_FILE},
+    packer,
+    reader::MAX_MULTIPLE_ACCOUNTS,
+    sdk::{
+        AccountDecoder, BlobHeader, ContentEntry, InstructionData, NameConfig, Network, PageData,
+    },
+    signer::{resolve_payer, resolve_signer, signer_from_source, BoxedSigner},
+    solana_trait::{BlockhashCache, RetryPolicy, SolanaTransaction},
+    sync::manifest_entry,
 };
 
+pub const DEFAULT_IN_FLIGHT: usize = 8;
+
+/// Trunks are numbered with a `u8`.
+const MAX_TRUNKS: usize = 256;
+
+/// Most trunks tried in one transaction when the trunk size is picked
+/// automatically.
+const MAX_TRUNKS_PER_TRANSACTION: usize = 4;
+
 pub struct W3Client {
     pub program: Pubkey,
-    pub signer: Keypair,
+    /// Content authority, signs every write and pays the rent of the accounts
+    /// it creates.
+    pub signer: BoxedSigner,
+    /// Pays the transaction fees, the authority when unset.
+    pub payer: Option<BoxedSigner>,
     pub network: Network,
+    /// Bytes per trunk, zero picks the size and trunks per transaction
+    /// needing the fewest transactions.
     pub trunk_size: usize,
+    /// Upload files as content-addressed blobs shared between paths.
+    pub dedup: bool,
+    /// Transactions awaiting confirmation at the same time while uploading.
+    pub in_flight: usize,
+    pub retry: RetryPolicy,
+    /// Include and exclude globs applied when collecting files.
+    pub filter: FileFilter,
+    /// Compute-unit price and priority spend cap of the transactions sent.
+    pub fees: FeePolicy,
+    /// Globs of the files uploaded even though they look like secrets.
+    pub allow_secrets: Vec<Pattern>,
+    /// Address lookup table of the site, see the `lookup` module.
+    pub lookup_table: Option<Pubkey>,
+    /// Lookup tables of the transactions too large for a legacy message.
+    pub lookup_tables: Vec<AddressLookupTableAccount>,
     pub connection: RpcClient,
+    pub blockhash: BlockhashCache,
     pub helper: PdaHelper,
+    /// Derives the page accounts read back, those of the name passed to
+    /// `read_name` or else the ones written.
+    pub content: PdaHelper,
 }
 
 impl W3Client {
-    pub fn new(program: Pubkey, signer: Keypair, network: Network, trunk_size: usize) -> Self {
+    pub fn new(program: Pubkey, signer: BoxedSigner, network: Network, trunk_size: usize) -> Self {
         Self {
             program,
             signer,
+            payer: None,
             network: network.clone(),
             trunk_size,
+            dedup: false,
+            in_flight: DEFAULT_IN_FLIGHT,
+            retry: RetryPolicy::default(),
+            filter: FileFilter::default(),
+            fees: FeePolicy::default(),
+            allow_secrets: Vec::new(),
+            lookup_table: None,
+            lookup_tables: Vec::new(),
             connection: network.get_rpc_client(),
+            blockhash: BlockhashCache::default(),
             helper: PdaHelper::new(program),
+            content: PdaHelper::new(program),
         }
     }
 
-    pub fn from_args(program: String, network: String, account: String) -> Self {
-        let program = Pubkey::from_str(program.as_str()).unwrap();
-        let signer = Keypair::from_base58_string(account.as_str());
+    pub fn from_args(program: String, network: String, account: String) -> W3Result<Self> {
+        let program = Pubkey::from_str(program.as_str())
+            .map_err(|e| W3ClientError::InvalidArgument(format!("program {}: {}", program, e)))?;
+        let signer = match account.as_str() {
+            "" => resolve_signer("")?,
+            source => signer_from_source(source)?,
+        };
         let network = &Network::from_string(network.as_str());
-        Self {
+        Ok(Self {
             program,
             signer,
+            payer: resolve_payer(None)?,
             network: network.clone(),
-            trunk_size: 512,
+            trunk_size: 0,
+            dedup: false,
+            in_flight: DEFAULT_IN_FLIGHT,
+            retry: RetryPolicy::default(),
+            filter: FileFilter::default(),
+            fees: FeePolicy::default(),
+            allow_secrets: Vec::new(),
+            lookup_table: None,
+            lookup_tables: Vec::new(),
             connection: network.get_rpc_client(),
+            blockhash: BlockhashCache::default(),
             helper: PdaHelper::new(program),
+            content: PdaHelper::new(program),
+        })
+    }
+
+    /// Signer paying the transaction fees.
+    pub fn fee_payer(&self) -> &dyn Signer {
+        match &self.payer {
+            Some(payer) => payer.as_ref(),
+            None => self.signer.as_ref(),
+        }
+    }
+
+    /// Signers of every transaction, the fee payer first.
+    pub fn signers(&self) -> Vec<&dyn Signer> {
+        let mut signers = vec![self.fee_payer()];
+        if self.fee_payer().pubkey() != self.signer.pubkey() {
+            signers.push(self.signer.as_ref());
         }
+        signers
     }
 
-    pub fn say_hi(&self) {
-        println!("");
+    pub fn say_hi(&self) -> W3Result<()> {
+        println!();
         info!("🔥 Hello, W3Client! 🔥 ");
         info!("current account : {}", self.signer.pubkey());
+        if self.payer.is_some() {
+            info!("fee payer       : {}", self.fee_payer().pubkey());
+        }
         info!("current program : {:?}", self.program);
         info!("current network : {:?}", self.network.to_string());
 
-        match self.get_account_info(&self.signer.pubkey()) {
+        match self.get_account_info(&self.signer.pubkey())? {
             Some(account) => {
                 info!("account balance : {:?}", account.lamports);
             }
@@ -68,236 +167,833 @@ impl W3Client {
             }
         }
 
-        println!("");
+        println!();
+        Ok(())
+    }
+
+    pub fn get_name_config(&self, name: &str) -> W3Result<Option<NameConfig>> {
+        let (config_account, _) = self.helper.find_name_address(name);
+        match self.get_account_info(&config_account)? {
+            Some(account) => NameConfig::try_from_slice(&account.data)
+                .map(Some)
+                .map_err(|e| W3ClientError::Decode(format!("name config of {}: {}", name, e))),
+            None => Ok(None),
+        }
+    }
+
+    /// Helper deriving the page accounts of `name`, which live under the
+    /// program recorded in its `NameConfig` rather than under `self.program`.
+    pub fn content_helper(&self, name: &str) -> W3Result<PdaHelper> {
+        Ok(match self.get_name_config(name)? {
+            Some(config) => PdaHelper::new(config.program),
+            None => PdaHelper::new(self.program),
+        })
+    }
+
+    /// Reads pages from the program serving `name`, see `content_helper`.
+    pub fn read_name(&mut self, name: &str) -> W3Result<()> {
+        let content = self.content_helper(name)?;
+        if content.program != self.program {
+            info!("name {} is served by program {}", name, content.program);
+        }
+        self.content = content;
+        Ok(())
+    }
+
+    pub fn check_name_program(&self, name: &str) -> W3Result<()> {
+        match self.get_name_config(name)? {
+            Some(config) if config.program != self.program => {
+                warn!(
+                    "name {} resolves to program {}, but content is written to {}; uploaded pages will not be served under this name",
+                    name, config.program, self.program
+                );
+            }
+            Some(_) => {
+                info!("name {} resolves to current program", name);
+            }
+            None => {
+                warn!("name {} is not configured yet", name);
+            }
+        }
+        Ok(())
     }
 }
 
 impl W3Client {
-    pub fn visit_dirs(&self, dir: &Path, root_dir: &Path) -> std::io::Result<()> {
-        if dir.is_dir() {
-            for entry in fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                if path.is_dir() {
-                    // 如果是目录，则递归遍历
-                    self.visit_dirs(&path, root_dir)?;
-                } else {
-                    // 打印文件名
-                    if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                        if !file_name.starts_with(".") {
-                            let full_path = path.to_str().unwrap();
-
-                            let relative_path = path
-                                .strip_prefix(root_dir)
-                                .unwrap_or_else(|_| Path::new(""))
-                                .to_str()
-                                .unwrap_or("");
-
-                            info!("Found file: {} ", full_path);
-                            let web_path = format!("/{}", relative_path.replace("\\", "/")); // 确保路径使用 web 标准的斜杠
-                            info!("Web path: {}", web_path);
-
-                            self.upload_file(&web_path, full_path);
-                        }
-                    }
+    /// Uploads every file of `dir` and records them in the site manifest.
+    pub fn visit_dirs(&self, dir: &Path, root_dir: &Path) -> W3Result<UploadReport> {
+        let mut files = Vec::new();
+        self.collect_files(dir, root_dir, &mut files)?;
+        self.check_secrets(&files)?;
+        let jobs = self.upload_jobs(&files)?;
+        let report = self.run_journaled_jobs(jobs, root_dir)?;
+
+        let mut written = BTreeMap::new();
+        for (web_path, full_path) in &files {
+            if !report.failed.contains(web_path) {
+                written.insert(web_path.clone(), manifest_entry(&fs::read(full_path)?));
+            }
+        }
+        self.update_manifest(&written, &report.failed)?;
+        Ok(report)
+    }
+
+    /// Walks `dir` and returns `(web_path, full_path)` for every file to upload.
+    /// Dotfiles, files ignored by a `.w3ignore` and files left out by the
+    /// include and exclude globs are skipped.
+    pub fn collect_files(
+        &self,
+        dir: &Path,
+        root_dir: &Path,
+        files: &mut Vec<(String, String)>,
+    ) -> W3Result<()> {
+        self.walk_files(dir, root_dir, &mut IgnoreStack::default(), files)
+    }
+
+    fn walk_files(
+        &self,
+        dir: &Path,
+        root_dir: &Path,
+        ignores: &mut IgnoreStack,
+        files: &mut Vec<(String, String)>,
+    ) -> W3Result<()> {
+        if !dir.is_dir() {
+            return Ok(());
+        }
+        let has_ignore_file = ignores.enter(dir)?;
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let relative_path = path
+                .strip_prefix(root_dir)
+                .unwrap_or_else(|_| Path::new(""))
+                .to_str()
+                .unwrap_or("")
+                .replace('\\', "/"); // 确保路径使用 web 标准的斜杠
+            if path.is_dir() {
+                if ignores.is_ignored(&path, true) || !self.filter.enters(&relative_path) {
+                    info!("Skipped directory: {}", path.display());
+                    continue;
+                }
+                // 如果是目录，则递归遍历
+                self.walk_files(&path, root_dir, ignores, files)?;
+            } else if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
+                if file_name.starts_with('.') {
+                    continue;
                 }
+                if ignores.is_ignored(&path, false) || !self.filter.selects(&relative_path) {
+                    info!("Skipped file: {}", path.display());
+                    continue;
+                }
+                let full_path = path.to_string_lossy();
+                info!("Found file: {} ", full_path);
+                let web_path = format!("/{}", relative_path);
+                info!("Web path: {}", web_path);
+
+                files.push((web_path, full_path.to_string()));
             }
         }
+        if has_ignore_file {
+            ignores.leave();
+        }
         Ok(())
     }
 
-    pub fn config_name(&self, name: &str) {
+    pub fn config_name(&self, name: &str, default_page: &str) -> W3Result<Signature> {
         info!("Configuring with name: {}", name);
-        let config_seeds = vec![".w3-solana-name".as_bytes(), name.as_bytes()];
-        info!("config_seeds {:?}", config_seeds);
-        let (config_account, bump_seed) = self.helper.find_program_address(&config_seeds);
+        self.send_name_instruction(
+            name,
+            InstructionData::NameMapping {
+                name: name.to_string(),
+                program: self.program,
+                default_page: default_page.to_string(),
+            },
+        )
+    }
+
+    /// Points `name`, owned by the authority, at `program` and `default_page`.
+    pub fn update_name(
+        &self,
+        name: &str,
+        program: Pubkey,
+        default_page: &str,
+    ) -> W3Result<Signature> {
+        info!("Updating name {} to program {}", name, program);
+        self.send_name_instruction(
+            name,
+            InstructionData::UpdateName {
+                name: name.to_string(),
+                program,
+                default_page: default_page.to_string(),
+            },
+        )
+    }
+
+    /// Hands `name`, owned by the authority, over to `owner`.
+    pub fn transfer_name(&self, name: &str, owner: Pubkey) -> W3Result<Signature> {
+        info!("Transferring name {} to {}", name, owner);
+        self.send_name_instruction(
+            name,
+            InstructionData::TransferName {
+                name: name.to_string(),
+                owner,
+            },
+        )
+    }
+
+    fn send_name_instruction(&self, name: &str, data: InstructionData) -> W3Result<Signature> {
+        let (config_account, bump_seed) = self.helper.find_name_address(name);
         info!("Account: {}", config_account);
         info!("Bump seed: {}", bump_seed);
-        let instruction_enum = InstructionData::NameMapping {
-            name: name.to_string(),
-            program: self.program,
-            default_page: "/index.html".to_string(),
-        };
-
-        match instruction_enum.try_to_vec() {
-            Ok(instruction_data) => {
-                info!("do name config instruction ...");
-                let instruction = solana_sdk::instruction::Instruction {
-                    program_id: self.program,
-                    accounts: vec![
-                        AccountMeta {
-                            pubkey: self.signer.pubkey(),
-                            is_signer: true,
-                            is_writable: true,
-                        },
-                        AccountMeta {
-                            pubkey: config_account,
-                            is_signer: false,
-                            is_writable: true,
-                        },
-                        AccountMeta {
-                            pubkey: system_program::ID,
-                            is_signer: false,
-                            is_writable: false,
-                        },
-                    ],
-                    data: instruction_data,
-                };
-                self.send_instruction(&self.signer.pubkey(), &vec![&self.signer], instruction);
-            }
-            Err(e) => {
-                error!("Error serializing instruction: {:?}", e);
-            }
-        }
+        let instruction = Instruction::new_with_bytes(
+            self.program,
+            &data.try_to_vec()?,
+            vec![
+                AccountMeta::new(self.signer.pubkey(), true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        );
+        self.send_instruction(&self.fee_payer().pubkey(), &self.signers(), instruction)
     }
 
     pub fn deploy(&self) {
         info!("Deploying program...");
     }
 
+    fn program_instruction(
+        &self,
+        instruction_enum: InstructionData,
+        accounts: Vec<AccountMeta>,
+    ) -> W3Result<Instruction> {
+        Ok(Instruction {
+            program_id: self.program,
+            accounts,
+            data: instruction_enum.try_to_vec()?,
+        })
+    }
+
+    fn send_program_instruction(
+        &self,
+        instruction_enum: InstructionData,
+        accounts: Vec<AccountMeta>,
+    ) -> W3Result<Signature> {
+        let instruction = self.program_instruction(instruction_enum, accounts)?;
+        self.send_instruction(&self.fee_payer().pubkey(), &self.signers(), instruction)
+    }
+
     fn trunk_instruction(
-        self: &Self,
+        &self,
         web_path: &str,
         trunk: Vec<u8>,
         idx: u8,
         meta_account: &Pubkey,
         trunk_account: &Pubkey,
-    ) -> Result<solana_sdk::instruction::Instruction, String> {
-        let instruction_enum = InstructionData::PutTrunkContent {
-            path: web_path.to_string(),
-            trunk_no: idx,
-            body: trunk,
-        };
+    ) -> W3Result<Instruction> {
+        self.program_instruction(
+            InstructionData::PutTrunkContent {
+                path: web_path.to_string(),
+                trunk_no: idx,
+                body: trunk,
+            },
+            vec![
+                AccountMeta::new(self.signer.pubkey(), true),
+                AccountMeta::new(*meta_account, false),
+                AccountMeta::new(*trunk_account, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        )
+    }
 
-        match instruction_enum.try_to_vec() {
-            Ok(instruction_data) => Ok(solana_sdk::instruction::Instruction {
-                program_id: self.program,
-                accounts: vec![
-                    AccountMeta {
-                        pubkey: self.signer.pubkey(),
-                        is_signer: true,
-                        is_writable: true,
-                    },
-                    AccountMeta {
-                        pubkey: meta_account.clone(),
-                        is_signer: false,
-                        is_writable: true,
-                    },
-                    AccountMeta {
-                        pubkey: trunk_account.clone(),
-                        is_signer: false,
-                        is_writable: true,
-                    },
-                    AccountMeta {
-                        pubkey: system_program::ID,
-                        is_signer: false,
-                        is_writable: false,
-                    },
-                ],
-                data: instruction_data,
-            }),
-            Err(e) => Err(format!("Error serializing instruction: {:?}", e)),
+    fn simple_instruction(&self, web_path: &str, body: &[u8]) -> W3Result<Instruction> {
+        let (account, _) = self.helper.find_program_address_by_text(web_path);
+        self.program_instruction(
+            InstructionData::PutContent {
+                path: web_path.to_string(),
+                body: body.to_vec(),
+            },
+            vec![
+                AccountMeta::new(self.signer.pubkey(), true),
+                AccountMeta::new(account, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        )
+    }
+
+    fn batch_instruction(&self, entries: &[(String, Vec<u8>)]) -> W3Result<Instruction> {
+        let mut accounts = vec![
+            AccountMeta::new(self.signer.pubkey(), true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        for (web_path, _) in entries {
+            let (account, _) = self.helper.find_program_address_by_text(web_path);
+            accounts.push(AccountMeta::new(account, false));
+        }
+        self.program_instruction(
+            InstructionData::PutContentBatch {
+                entries: entries
+                    .iter()
+                    .map(|(path, body)| ContentEntry {
+                        path: path.clone(),
+                        body: body.clone(),
+                    })
+                    .collect(),
+            },
+            accounts,
+        )
+    }
+
+    /// Builds the upload jobs of `(web_path, full_path)` files. Files over
+    /// the blob size limit are written inline even with dedup on.
+    pub fn upload_jobs(&self, files: &[(String, String)]) -> W3Result<Vec<UploadJob>> {
+        let mut jobs = Vec::new();
+        let mut inline = Vec::new();
+        for (web_path, full_path) in files {
+            if self.dedup && fs::metadata(full_path)?.len() <= BlobHeader::MAX_SIZE as u64 {
+                jobs.extend(self.blob_job(web_path, full_path)?);
+            } else {
+                inline.push((web_path.clone(), full_path.clone()));
+            }
         }
+
+        let linked = self.linked_blobs(&inline)?;
+        let mut small_files = Vec::new();
+        for (web_path, full_path) in inline {
+            if let Some(hash) = linked.get(&web_path) {
+                jobs.push(self.inline_job(&web_path, &full_path, Some(*hash))?);
+            } else if fs::metadata(&full_path)?.len() as usize <= self.simple_size_for(&web_path) {
+                small_files.push((web_path, full_path));
+            } else {
+                jobs.push(self.file_job(&web_path, &full_path)?);
+            }
+        }
+        jobs.extend(self.batch_jobs(&small_files)?);
+        Ok(jobs)
     }
 
-    fn upload_simple(&self, web_path: &str, body: &Vec<u8>, account: Pubkey) {
-        info!("Uploading simple content of length {}", body.len());
-        let instruction_enum = InstructionData::PutContent {
-            path: web_path.to_string(),
-            body: body.clone(),
+    /// Blobs linked by the pages of `files`, keyed by web path.
+    fn linked_blobs(&self, files: &[(String, String)]) -> W3Result<HashMap<String, [u8; 32]>> {
+        let mut linked = HashMap::new();
+        for chunk in files.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let keys: Vec<Pubkey> = chunk
+                .iter()
+                .map(|(web_path, _)| self.helper.find_program_address_by_text(web_path).0)
+                .collect();
+            let accounts = self.connection.get_multiple_accounts(&keys)?;
+            for ((web_path, _), account) in chunk.iter().zip(accounts) {
+                if let Some(Ok(PageData::BlobPage { hash })) =
+                    account.map(|account| PageData::from_account_data(&account.data))
+                {
+                    linked.insert(web_path.clone(), hash);
+                }
+            }
+        }
+        Ok(linked)
+    }
+
+    /// Writes the file into the page itself, unlinking the blob the page
+    /// links first since the program refuses to overwrite a blob page.
+    pub fn inline_job(
+        &self,
+        web_path: &str,
+        full_path: &str,
+        linked: Option<[u8; 32]>,
+    ) -> W3Result<UploadJob> {
+        let mut job = self.file_job(web_path, full_path)?;
+        if let Some(hash) = linked {
+            job.stages
+                .insert(0, vec![vec![self.unlink_instruction(web_path, &hash)?]]);
+        }
+        Ok(job)
+    }
+
+    /// Packs small files into as few `PutContentBatch` transactions as fit in
+    /// the packet limit, as v0 ones when lookup tables are loaded. Files too
+    /// big for any batch fall back to `PutContent`.
+    pub fn batch_jobs(&self, files: &[(String, String)]) -> W3Result<Vec<UploadJob>> {
+        let payer = self.fee_payer().pubkey();
+        let reserved = self.fees.reserved_size();
+        let fits = |entries: &[(String, Vec<u8>)]| match self.batch_instruction(entries) {
+            Ok(instruction) => {
+                let instructions = [instruction];
+                packer::fits_in_packet(&instructions, &payer, reserved)
+                    || (!self.lookup_tables.is_empty()
+                        && packer::fits_in_versioned_packet(
+                            &instructions,
+                            &payer,
+                            &self.lookup_tables,
+                            reserved,
+                        ))
+            }
+            Err(_) => false,
         };
+        let mut batches: Vec<Vec<(String, Vec<u8>)>> = Vec::new();
+        let mut jobs = Vec::new();
+        let mut pending: Vec<(String, Vec<u8>)> = Vec::new();
+        for (web_path, full_path) in files {
+            let body = fs::read(full_path)?;
+            pending.push((web_path.clone(), body));
+            if fits(&pending) {
+                continue;
+            }
 
-        match instruction_enum.try_to_vec() {
-            Ok(instruction_data) => {
-                let instruction = solana_sdk::instruction::Instruction {
-                    program_id: self.program,
-                    accounts: vec![
-                        AccountMeta {
-                            pubkey: self.signer.pubkey(),
-                            is_signer: true,
-                            is_writable: true,
-                        },
-                        AccountMeta {
-                            pubkey: account,
-                            is_signer: false,
-                            is_writable: true,
-                        },
-                        AccountMeta {
-                            pubkey: system_program::ID,
-                            is_signer: false,
-                            is_writable: false,
-                        },
-                    ],
-                    data: instruction_data,
-                };
-                self.send_instruction(&self.signer.pubkey(), &vec![&self.signer], instruction);
+            let entry = pending.pop().unwrap();
+            if !pending.is_empty() {
+                batches.push(std::mem::take(&mut pending));
             }
-            Err(e) => {
-                error!("Error serializing instruction: {:?}", e);
+            if fits(std::slice::from_ref(&entry)) {
+                pending.push(entry);
+            } else {
+                let instruction = self.simple_instruction(&entry.0, &entry.1)?;
+                jobs.push(UploadJob::new(&entry.0).stage(vec![vec![instruction]]));
             }
         }
+        if !pending.is_empty() {
+            batches.push(pending);
+        }
+
+        for batch in batches {
+            let paths = batch.iter().map(|(path, _)| path.clone()).collect();
+            let instruction = self.batch_instruction(&batch)?;
+            jobs.push(UploadJob::batch(paths).stage(vec![vec![instruction]]));
+        }
+        Ok(jobs)
     }
 
-    fn upload_file(&self, web_path: &str, full_path: &str) {
+    pub fn file_job(&self, web_path: &str, full_path: &str) -> W3Result<UploadJob> {
+        self.content_job(web_path, fs::read(full_path)?)
+    }
+
+    /// Chunked content writes its trunks in any order and the last trunk once
+    /// every other one landed, since the page meta records the last trunk.
+    /// The trunks sharing the final transaction are written with the last
+    /// one, so a small file may take a single transaction. With an automatic
+    /// trunk size, the size and trunks per transaction needing the fewest
+    /// transactions, then the fewest trunks, are picked together.
+    pub fn content_job(&self, web_path: &str, file_data: Vec<u8>) -> W3Result<UploadJob> {
         let (account, bump_seed) = self.helper.find_program_address_by_text(web_path);
         info!("Account: {}", account);
         info!("Bump seed: {}", bump_seed);
-        let body_trunks = {
-            let file_data: Vec<u8> = fs::read(full_path).unwrap();
-            info!("Data length: {}", file_data.len());
-            if file_data.len() > self.trunk_size {
-                warn!("Data too long, truncating to {} bytes", self.trunk_size);
-                file_data
-                    .chunks(self.trunk_size)
-                    .map(|c| c.to_vec())
-                    .collect()
-            } else {
-                vec![file_data]
+        info!("Data length: {}", file_data.len());
+        if file_data.len() <= self.simple_size_for(web_path) {
+            let instruction = self.simple_instruction(web_path, &file_data)?;
+            return Ok(UploadJob::new(web_path).stage(vec![vec![instruction]]));
+        }
+
+        let per_transaction = match self.trunk_size {
+            0 => MAX_TRUNKS_PER_TRANSACTION,
+            _ => 1,
+        };
+        let mut best: Option<(usize, usize, UploadJob)> = None;
+        for trunk_size in (1..=per_transaction).map(|count| self.trunk_size_for(web_path, count)) {
+            if trunk_size == 0 || file_data.len().div_ceil(trunk_size) > MAX_TRUNKS {
+                continue;
             }
+            let job = self.trunk_job(web_path, &file_data, trunk_size)?;
+            let transactions = job.stages.iter().map(Vec::len).sum();
+            if best
+                .as_ref()
+                .is_none_or(|(fewest, ..)| transactions < *fewest)
+            {
+                best = Some((transactions, trunk_size, job));
+            }
+        }
+        let Some((_, trunk_size, job)) = best else {
+            return Err(W3ClientError::InvalidArgument(format!(
+                "{} needs more than {} trunks of {} bytes",
+                web_path,
+                MAX_TRUNKS,
+                self.trunk_size_for(web_path, 1)
+            )));
         };
+        info!("Trunk size: {}", trunk_size);
+        Ok(job)
+    }
 
-        let trunk_count = body_trunks.len();
+    /// Job writing `file_data` in trunks of `trunk_size`, its final
+    /// transaction holding the last trunk and as many trunks before it as fit.
+    fn trunk_job(
+        &self,
+        web_path: &str,
+        file_data: &[u8],
+        trunk_size: usize,
+    ) -> W3Result<UploadJob> {
+        let (account, _) = self.helper.find_program_address_by_text(web_path);
+        let mut trunks = Vec::new();
+        for (idx, trunk) in file_data.chunks(trunk_size).enumerate() {
+            let (trunk_account, _) = self
+                .helper
+                .find_program_address_by_text_suffix(web_path, &[idx as u8]);
+            trunks.push(self.trunk_instruction(
+                web_path,
+                trunk.to_vec(),
+                idx as u8,
+                &account,
+                &trunk_account,
+            )?);
+        }
 
-        if trunk_count > 1 {
-            // let mut instructions = Vec::new();
-            for (idx, trunk) in body_trunks.iter().enumerate() {
-                let (trunk_account, _) = self
-                    .helper
-                    .find_program_address_by_text_suffix(web_path, &vec![idx as u8]);
-
-                match self.trunk_instruction(
-                    web_path,
-                    trunk.clone(),
-                    idx as u8,
-                    &account,
-                    &trunk_account,
-                ) {
-                    Ok(instruction) => {
-                        info!("Sending instruction for trunk {} / {}", idx, trunk_count);
-                        // instructions.push(instruction);
-                        self.send_instruction(
-                            &self.signer.pubkey(),
-                            &vec![&self.signer],
-                            instruction,
-                        );
-                    }
-                    Err(e) => {
-                        error!("{}", e);
-                    }
+        let payer = self.fee_payer().pubkey();
+        let reserved = self.fees.reserved_size();
+        let mut split = trunks.len().saturating_sub(1);
+        while split > 0 && packer::fits_in_packet(&trunks[split - 1..], &payer, reserved) {
+            split -= 1;
+        }
+        let last = trunks.split_off(split);
+        Ok(UploadJob::new(web_path)
+            .stage(packer::pack_instructions(trunks, &payer, reserved))
+            .stage(vec![last]))
+    }
+
+    /// Largest body written with a single `PutContent` for `web_path`.
+    pub fn simple_size_for(&self, web_path: &str) -> usize {
+        let (account, _) = self.helper.find_program_address_by_text(web_path);
+        let reserved = self.fees.reserved_size();
+        let limit =
+            packer::max_body_size(&self.fee_payer().pubkey(), reserved, |body| Instruction {
+                program_id: self.program,
+                accounts: vec![
+                    AccountMeta::new(self.signer.pubkey(), true),
+                    AccountMeta::new(account, false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                ],
+                data: InstructionData::PutContent {
+                    path: web_path.to_string(),
+                    body,
+                }
+                .to_bytes(),
+            });
+        match self.trunk_size {
+            0 => limit,
+            trunk_size => trunk_size.min(limit),
+        }
+    }
+
+    /// Trunk size of `web_path` when `count` trunks share a transaction,
+    /// longer paths leave less room for the body.
+    pub fn trunk_size_for(&self, web_path: &str, count: usize) -> usize {
+        let (account, _) = self.helper.find_program_address_by_text(web_path);
+        let reserved = self.fees.reserved_size();
+        let limit = packer::max_shared_body_size(
+            &self.fee_payer().pubkey(),
+            reserved,
+            count,
+            |idx, body| Instruction {
+                program_id: self.program,
+                accounts: vec![
+                    AccountMeta::new(self.signer.pubkey(), true),
+                    AccountMeta::new(account, false),
+                    AccountMeta::new(Pubkey::new_unique(), false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                ],
+                data: InstructionData::PutTrunkContent {
+                    path: web_path.to_string(),
+                    trunk_no: idx as u8,
+                    body,
                 }
+                .to_bytes(),
+            },
+        );
+        match self.trunk_size {
+            0 => limit,
+            trunk_size => trunk_size.min(limit),
+        }
+    }
 
-                // if instructions.len() > 0 {
-                //     self.send_instructions(
-                //         &self.signer.pubkey(),
-                //         &vec![&self.signer],
-                //         instructions.clone(),
-                //     );
-                // }
+    fn blob_chunk_size(&self) -> usize {
+        let reserved = self.fees.reserved_size();
+        let limit =
+            packer::max_body_size(&self.fee_payer().pubkey(), reserved, |body| Instruction {
+                program_id: self.program,
+                accounts: vec![
+                    AccountMeta::new(self.signer.pubkey(), true),
+                    AccountMeta::new(Pubkey::new_unique(), false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                ],
+                data: InstructionData::PutBlob {
+                    hash: [0; 32],
+                    size: 0,
+                    offset: 0,
+                    body,
+                }
+                .to_bytes(),
+            });
+        match self.trunk_size {
+            0 => limit,
+            trunk_size => trunk_size.min(limit),
+        }
+    }
+
+    pub fn get_blob_header(&self, hash: &[u8; 32]) -> W3Result<Option<BlobHeader>> {
+        let (blob_account, _) = self.helper.find_blob_address(hash);
+        Ok(self
+            .get_account_info(&blob_account)?
+            .and_then(|account| BlobHeader::from_account_data(&account.data)))
+    }
+
+    fn get_linked_blob(&self, web_path: &str) -> W3Result<Option<[u8; 32]>> {
+        Ok(match self.get_page_data(web_path)? {
+            Some(PageData::BlobPage { hash }) => Some(hash),
+            _ => None,
+        })
+    }
+
+    /// Uploads the file as a blob keyed by its sha256 unless a sealed blob
+    /// with the same content is already on chain, then links the page to it.
+    /// A chunked page is deleted first so its trunks are closed.
+    /// Returns `None` when the page already links that blob.
+    pub fn blob_job(&self, web_path: &str, full_path: &str) -> W3Result<Option<UploadJob>> {
+        let file_data: Vec<u8> = fs::read(full_path)?;
+        if file_data.len() > BlobHeader::MAX_SIZE as usize {
+            return Err(W3ClientError::InvalidArgument(format!(
+                "{} is {} bytes, blobs hold at most {}",
+                full_path,
+                file_data.len(),
+                BlobHeader::MAX_SIZE
+            )));
+        }
+        let hash = hash(&file_data).to_bytes();
+        let (blob_account, _) = self.helper.find_blob_address(&hash);
+        info!("Blob account: {} size: {}", blob_account, file_data.len());
+
+        let mut chunks = Vec::new();
+        match self.get_blob_header(&hash)? {
+            Some(header) if header.sealed => {
+                info!(
+                    "Blob already on chain, skip upload ({} refs)",
+                    header.ref_count
+                );
+            }
+            // only its creator writes a blob until it is sealed
+            Some(header) if header.creator != self.signer.pubkey() => {
+                warn!(
+                    "Blob {} is being written by {}, upload {} inline",
+                    blob_account, header.creator, web_path
+                );
+                let linked = self.get_linked_blob(web_path)?;
+                return self.inline_job(web_path, full_path, linked).map(Some);
+            }
+            _ => {
+                let chunk_size = self.blob_chunk_size();
+                let bodies: Vec<&[u8]> = if file_data.is_empty() {
+                    vec![&[]]
+                } else {
+                    file_data.chunks(chunk_size).collect()
+                };
+                for (idx, body) in bodies.iter().enumerate() {
+                    chunks.push(self.program_instruction(
+                        InstructionData::PutBlob {
+                            hash,
+                            size: file_data.len() as u32,
+                            offset: (idx * chunk_size) as u32,
+                            body: body.to_vec(),
+                        },
+                        vec![
+                            AccountMeta::new(self.signer.pubkey(), true),
+                            AccountMeta::new(blob_account, false),
+                            AccountMeta::new_readonly(system_program::ID, false),
+                        ],
+                    )?);
+                }
+            }
+        }
+
+        let (page_account, _) = self.helper.find_program_address_by_text(web_path);
+        let mut accounts = vec![
+            AccountMeta::new(self.signer.pubkey(), true),
+            AccountMeta::new(page_account, false),
+            AccountMeta::new(blob_account, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        let mut delete = Vec::new();
+        match self.get_page_data(web_path)? {
+            Some(PageData::BlobPage { hash: previous }) => {
+                if previous == hash {
+                    info!("Page {} already links this blob", web_path);
+                    return Ok(None);
+                }
+                let (previous_blob_account, _) = self.helper.find_blob_address(&previous);
+                accounts.push(AccountMeta::new(previous_blob_account, false));
+            }
+            Some(page @ PageData::TrunkPage { .. }) => {
+                delete.push(self.delete_instruction(web_path, &page)?);
             }
-            // info!("Trunk account: {}", trunk_account);
-            // self.upload_trunk(web_path, trunk.clone(), idx as u8, &account, &trunk_account);
-        } else {
-            self.upload_simple(web_path, &body_trunks[0], account)
+            _ => {}
         }
+        let link = self.program_instruction(
+            InstructionData::LinkBlob {
+                path: web_path.to_string(),
+                hash,
+            },
+            accounts,
+        )?;
+        let payer = self.fee_payer().pubkey();
+        let mut job = UploadJob::new(web_path).stage(packer::pack_instructions(
+            chunks,
+            &payer,
+            self.fees.reserved_size(),
+        ));
+        // the closed page is only reassigned once its transaction completes
+        if !delete.is_empty() {
+            job = job.stage(vec![delete]);
+        }
+        Ok(Some(job.stage(vec![vec![link]])))
+    }
+
+    pub fn upload_blob_file(&self, web_path: &str, full_path: &str) -> W3Result<UploadReport> {
+        let jobs = self.blob_job(web_path, full_path)?.into_iter().collect();
+        Ok(self.run_jobs(jobs))
+    }
+
+    pub fn run_jobs(&self, jobs: Vec<UploadJob>) -> UploadReport {
+        let report = UploadEngine::new(self, self.in_flight).run(jobs);
+        self.log_report(&report);
+        report
+    }
+
+    fn log_report(&self, report: &UploadReport) {
+        for (web_path, e) in &report.errors {
+            error!("Failed to upload {} : {}", web_path, e);
+        }
+        info!(
+            "{} uploads succeeded, {} failed",
+            report.succeeded.len(),
+            report.failed.len()
+        );
+        if self.fees.price != PriorityFee::None {
+            info!("{} lamports spent on priority fees", self.fees.spent());
+        }
+    }
+
+    /// Runs `jobs` with the journal of `dir`, resuming an interrupted upload
+    /// of the same files. The journal is removed once every job succeeded.
+    pub fn run_journaled_jobs(&self, jobs: Vec<UploadJob>, dir: &Path) -> W3Result<UploadReport> {
+        let journal = UploadJournal::open(dir)?;
+        if !journal.is_empty() {
+            info!("Resuming upload from {}", dir.join(JOURNAL_FILE).display());
+            journal.verify(self)?;
+        }
+        let report = UploadEngine::new(self, self.in_flight)
+            .journal(&journal)
+            .run(jobs);
+        self.log_report(&report);
+        if report.failed.is_empty() {
+            journal.finish()?;
+        }
+        Ok(report)
+    }
+
+    pub fn unlink_blob(&self, web_path: &str) -> W3Result<Signature> {
+        let hash = self.get_linked_blob(web_path)?.ok_or_else(|| {
+            W3ClientError::NotFound(format!("page {} does not link a blob", web_path))
+        })?;
+        let instruction = self.unlink_instruction(web_path, &hash)?;
+        self.send_instruction(&self.fee_payer().pubkey(), &self.signers(), instruction)
+    }
+
+    pub fn unlink_instruction(&self, web_path: &str, hash: &[u8; 32]) -> W3Result<Instruction> {
+        let (page_account, _) = self.helper.find_program_address_by_text(web_path);
+        let (blob_account, _) = self.helper.find_blob_address(hash);
+        self.program_instruction(
+            InstructionData::UnlinkBlob {
+                path: web_path.to_string(),
+            },
+            vec![
+                AccountMeta::new(self.signer.pubkey(), true),
+                AccountMeta::new(page_account, false),
+                AccountMeta::new(blob_account, false),
+            ],
+        )
+    }
+
+    pub fn close_blob(&self, hash: [u8; 32]) -> W3Result<Signature> {
+        let (blob_account, _) = self.helper.find_blob_address(&hash);
+        self.send_program_instruction(
+            InstructionData::CloseBlob { hash },
+            vec![
+                AccountMeta::new(self.signer.pubkey(), true),
+                AccountMeta::new(blob_account, false),
+            ],
+        )
+    }
+
+    /// Deletes the page at `web_path` together with its trunk accounts,
+    /// blob pages are unlinked instead.
+    pub fn delete_content(&self, web_path: &str) -> W3Result<Signature> {
+        let page = self
+            .get_page_data(web_path)?
+            .ok_or_else(|| W3ClientError::NotFound(format!("page {}", web_path)))?;
+        if let PageData::BlobPage { .. } = page {
+            return self.unlink_blob(web_path);
+        }
+        let instruction = self.delete_instruction(web_path, &page)?;
+        self.send_instruction(&self.fee_payer().pubkey(), &self.signers(), instruction)
+    }
+
+    /// Hands the page at `web_path`, written before pages recorded their
+    /// authority, to `authority`. The signer must be the upgrade authority of
+    /// the program.
+    pub fn claim_page(&self, web_path: &str, authority: Pubkey) -> W3Result<Signature> {
+        let (page_account, _) = self.helper.find_program_address_by_text(web_path);
+        let (program_data, _) =
+            Pubkey::find_program_address(&[self.program.as_ref()], &bpf_loader_upgradeable::id());
+        self.send_program_instruction(
+            InstructionData::ClaimPage {
+                path: web_path.to_string(),
+                authority,
+            },
+            vec![
+                AccountMeta::new(self.signer.pubkey(), true),
+                AccountMeta::new(page_account, false),
+                AccountMeta::new_readonly(program_data, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        )
+    }
+
+    /// `DeleteContent` of the page `page` stored at `web_path`.
+    fn delete_instruction(&self, web_path: &str, page: &PageData) -> W3Result<Instruction> {
+        let (page_account, _) = self.helper.find_program_address_by_text(web_path);
+        let mut accounts = vec![
+            AccountMeta::new(self.signer.pubkey(), true),
+            AccountMeta::new(page_account, false),
+        ];
+        if let PageData::TrunkPage { trunks } = *page {
+            for trunk_no in 0..=trunks {
+                let (trunk_account, _) = self
+                    .helper
+                    .find_program_address_by_text_suffix(web_path, &[trunk_no]);
+                accounts.push(AccountMeta::new(trunk_account, false));
+            }
+        }
+        self.program_instruction(
+            InstructionData::DeleteContent {
+                path: web_path.to_string(),
+            },
+            accounts,
+        )
+    }
+
+    /// Makes `web_path` serve the live data of `account` through `decoder`.
+    pub fn put_account_page(
+        &self,
+        web_path: &str,
+        account: Pubkey,
+        decoder: AccountDecoder,
+    ) -> W3Result<Signature> {
+        let (page_account, _) = self.helper.find_program_address_by_text(web_path);
+        info!("Account page {} -> {} ({:?})", web_path, account, decoder);
+        self.send_program_instruction(
+            InstructionData::PutAccountPage {
+                path: web_path.to_string(),
+                account,
+                decoder,
+            },
+            vec![
+                AccountMeta::new(self.signer.pubkey(), true),
+                AccountMeta::new(page_account, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        )
     }
 }
@@ -0,0 +1,506 @@
+//! Layered uploader configuration.
+//!
+//! Every setting is taken from the last layer defining it:
+//! 1. built-in defaults,
+//! 2. the user config, `$XDG_CONFIG_HOME/w3-uploader/config.yaml` or
+//!    `~/.config/w3-uploader/config.yaml`,
+//! 3. the project config given by `--config`, or the first `w3-uploader.yaml`
+//!    found walking up from the current directory,
+//! 4. `W3_*` environment variables,
+//! 5. command line flags.
+//!
+//! A config file may define named profiles under `profiles`, the one picked
+//! with `--profile` or `W3_PROFILE` is applied right after the settings of
+//! its file:
+//!
+//! ```yaml
+//! network: localnet
+//! profiles:
+//!   prod:
+//!     network: mainnet
+//!     name: mysite
+//!     dir: ./dist
+//!     exclude: ["*.map"]
+//! ```
+
+use {
+    log::info,
+    serde::Deserialize,
+    solana_program::pubkey::Pubkey,
+    solana_sdk::signature::Keypair,
+    std::{
+        collections::BTreeMap,
+        env, fmt, fs,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+use crate::{
+    client::{W3Client, DEFAULT_IN_FLIGHT},
+    error::{W3ClientError, W3Result},
+    fees::{FeePolicy, PriorityFee},
+    filter::{parse_globs, FileFilter},
+    sdk::Network,
+    signer::{resolve_signer, signer_from_source, BoxedSigner, KEYPAIR_ENV, PAYER_ENV},
+    solana_trait::RetryPolicy,
+};
+
+/// File name of the project config.
+pub const PROJECT_CONFIG: &str = "w3-uploader.yaml";
+
+pub const DEFAULT_PROGRAM: &str = "9pW59BsNCqtQC1xucwTXYS4Qe9qz5AgSy2jajE63odQb";
+pub const DEFAULT_NETWORK: &str = "localnet";
+pub const DEFAULT_NAME: &str = "w3sol";
+
+pub const PROFILE_ENV: &str = "W3_PROFILE";
+
+/// Settings defined by one layer, `None` leaves the value of the layers below.
+#[derive(Deserialize, Default, Debug, Clone)]
+pub struct ConfigLayer {
+    pub program: Option<String>,
+    pub network: Option<String>,
+    /// Signer source of the content authority, see the `signer` module.
+    pub signer: Option<String>,
+    /// Signer source of the fee payer.
+    pub payer: Option<String>,
+    pub trunk_size: Option<usize>,
+    pub dedup: Option<bool>,
+    pub in_flight: Option<usize>,
+    pub retries: Option<usize>,
+    /// Compute-unit price in micro-lamports, `auto` or `none`.
+    pub priority_fee: Option<PriorityFee>,
+    /// Most lamports spent on priority fees by one run.
+    pub max_priority_spend: Option<u64>,
+    /// Address lookup table of the site, see the `lookup` module.
+    pub lookup_table: Option<String>,
+    pub prune: Option<bool>,
+    /// Name the uploaded content is served under.
+    pub name: Option<String>,
+    /// Directory to upload.
+    pub dir: Option<PathBuf>,
+    /// Globs of the files to upload, see the `filter` module.
+    pub include: Option<Vec<String>>,
+    /// Globs of the files never uploaded.
+    pub exclude: Option<Vec<String>>,
+    /// Globs of the files uploaded even though they look like secrets.
+    pub allow_secrets: Option<Vec<String>>,
+    /// Profiles of a config file, ignored everywhere else.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ConfigLayer>,
+}
+
+impl ConfigLayer {
+    pub fn read(path: &Path) -> W3Result<Self> {
+        let data = fs::read(path)?;
+        let mut layer: Self = serde_yaml::from_slice(&data)
+            .map_err(|e| W3ClientError::Config(format!("{}: {}", path.display(), e)))?;
+        if let Some(dir) = path.parent() {
+            layer.resolve_paths(dir);
+        }
+        Ok(layer)
+    }
+
+    /// Makes paths relative to the config file work from any directory.
+    fn resolve_paths(&mut self, dir: &Path) {
+        self.signer = self.signer.take().map(|source| relative_to(dir, source));
+        self.payer = self.payer.take().map(|source| relative_to(dir, source));
+        self.dir = self
+            .dir
+            .take()
+            .map(|path| dir.join(path.strip_prefix(".").unwrap_or(&path)));
+        for profile in self.profiles.values_mut() {
+            profile.resolve_paths(dir);
+        }
+    }
+
+    /// Layer of the `W3_*` environment variables.
+    pub fn from_env() -> W3Result<Self> {
+        Ok(Self {
+            program: env::var("W3_PROGRAM").ok(),
+            network: env::var("W3_NETWORK").ok(),
+            signer: env::var(KEYPAIR_ENV).ok(),
+            payer: env::var(PAYER_ENV).ok(),
+            trunk_size: env_value("W3_TRUNK_SIZE")?,
+            dedup: env_value("W3_DEDUP")?,
+            in_flight: env_value("W3_IN_FLIGHT")?,
+            retries: env_value("W3_RETRIES")?,
+            priority_fee: env_value("W3_PRIORITY_FEE")?,
+            max_priority_spend: env_value("W3_MAX_PRIORITY_SPEND")?,
+            lookup_table: env::var("W3_LOOKUP_TABLE").ok(),
+            prune: env_value("W3_PRUNE")?,
+            name: env::var("W3_NAME").ok(),
+            dir: env::var_os("W3_DIR").map(PathBuf::from),
+            include: env_list("W3_INCLUDE"),
+            exclude: env_list("W3_EXCLUDE"),
+            allow_secrets: env_list("W3_ALLOW_SECRETS"),
+            profiles: BTreeMap::new(),
+        })
+    }
+}
+
+fn env_value<T: FromStr>(name: &str) -> W3Result<Option<T>>
+where
+    T::Err: fmt::Display,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|e| W3ClientError::Config(format!("{}={}: {}", name, value, e))),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Comma separated values of `name`.
+fn env_list(name: &str) -> Option<Vec<String>> {
+    env::var(name).ok().map(|value| {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+/// Layer a setting was taken from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    UserConfig(PathBuf),
+    ProjectConfig(PathBuf),
+    /// A profile of the config file described by the boxed source.
+    Profile(String, Box<Source>),
+    Env,
+    CommandLine,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::UserConfig(path) => write!(f, "user config {}", path.display()),
+            Self::ProjectConfig(path) => write!(f, "project config {}", path.display()),
+            Self::Profile(name, file) => write!(f, "profile {} of {}", name, file),
+            Self::Env => write!(f, "environment"),
+            Self::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value,
+            source: Source::Default,
+        }
+    }
+
+    fn apply(&mut self, value: Option<T>, source: &Source) {
+        if let Some(value) = value {
+            self.value = value;
+            self.source = source.clone();
+        }
+    }
+}
+
+/// Effective configuration with the layer of every value.
+#[derive(Debug, Clone)]
+pub struct W3Config {
+    pub program: Setting<String>,
+    pub network: Setting<String>,
+    /// Empty falls back to the Solana CLI keypair.
+    pub signer: Setting<String>,
+    pub payer: Setting<Option<String>>,
+    pub trunk_size: Setting<usize>,
+    pub dedup: Setting<bool>,
+    pub in_flight: Setting<usize>,
+    pub retries: Setting<usize>,
+    pub priority_fee: Setting<PriorityFee>,
+    pub max_priority_spend: Setting<Option<u64>>,
+    pub lookup_table: Setting<Option<String>>,
+    pub prune: Setting<bool>,
+    pub name: Setting<String>,
+    pub dir: Setting<PathBuf>,
+    pub include: Setting<Vec<String>>,
+    pub exclude: Setting<Vec<String>>,
+    pub allow_secrets: Setting<Vec<String>>,
+    /// Profile applied on top of each config file.
+    pub profile: Option<String>,
+}
+
+impl Default for W3Config {
+    fn default() -> Self {
+        Self {
+            program: Setting::new(DEFAULT_PROGRAM.to_string()),
+            network: Setting::new(DEFAULT_NETWORK.to_string()),
+            signer: Setting::new(String::new()),
+            payer: Setting::new(None),
+            trunk_size: Setting::new(0),
+            dedup: Setting::new(false),
+            in_flight: Setting::new(DEFAULT_IN_FLIGHT),
+            retries: Setting::new(RetryPolicy::default().retries),
+            priority_fee: Setting::new(PriorityFee::None),
+            max_priority_spend: Setting::new(None),
+            lookup_table: Setting::new(None),
+            prune: Setting::new(false),
+            name: Setting::new(DEFAULT_NAME.to_string()),
+            dir: Setting::new(PathBuf::from(".")),
+            include: Setting::new(Vec::new()),
+            exclude: Setting::new(Vec::new()),
+            allow_secrets: Setting::new(Vec::new()),
+            profile: None,
+        }
+    }
+}
+
+impl W3Config {
+    /// Merges every layer, `config` replaces the project config search,
+    /// `profile` overrides `W3_PROFILE` and `cli` holds the command line flags.
+    pub fn load(config: Option<&Path>, profile: Option<&str>, cli: ConfigLayer) -> W3Result<Self> {
+        let mut merged = Self {
+            profile: profile
+                .map(str::to_string)
+                .or_else(|| env::var(PROFILE_ENV).ok()),
+            ..Self::default()
+        };
+        let mut found = false;
+        if let Some(path) = user_config_path().filter(|path| path.is_file()) {
+            found |= merged.apply_file(ConfigLayer::read(&path)?, Source::UserConfig(path));
+        }
+        let project = match config {
+            Some(path) if !path.is_file() => {
+                return Err(W3ClientError::Config(format!(
+                    "config {} not found",
+                    path.display()
+                )))
+            }
+            Some(path) => Some(path.to_path_buf()),
+            None => find_project_config(&env::current_dir()?),
+        };
+        if let Some(path) = project {
+            info!("Using config {}", path.display());
+            found |= merged.apply_file(ConfigLayer::read(&path)?, Source::ProjectConfig(path));
+        }
+        if let (Some(profile), false) = (&merged.profile, found) {
+            return Err(W3ClientError::Config(format!(
+                "profile {} is not defined in any config file",
+                profile
+            )));
+        }
+        merged.apply(ConfigLayer::from_env()?, Source::Env);
+        merged.apply(cli, Source::CommandLine);
+        Ok(merged)
+    }
+
+    /// Applies a config file then its selected profile, returns whether the
+    /// file defines that profile.
+    pub fn apply_file(&mut self, mut layer: ConfigLayer, source: Source) -> bool {
+        let profile = self
+            .profile
+            .as_ref()
+            .and_then(|name| Some((name.clone(), layer.profiles.remove(name)?)));
+        self.apply(layer, source.clone());
+        match profile {
+            Some((name, profile)) => {
+                self.apply(profile, Source::Profile(name, Box::new(source)));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn apply(&mut self, layer: ConfigLayer, source: Source) {
+        self.program.apply(layer.program, &source);
+        self.network.apply(layer.network, &source);
+        self.signer.apply(layer.signer, &source);
+        self.payer.apply(layer.payer.map(Some), &source);
+        self.trunk_size.apply(layer.trunk_size, &source);
+        self.dedup.apply(layer.dedup, &source);
+        self.in_flight.apply(layer.in_flight, &source);
+        self.retries.apply(layer.retries, &source);
+        self.priority_fee.apply(layer.priority_fee, &source);
+        self.max_priority_spend
+            .apply(layer.max_priority_spend.map(Some), &source);
+        self.lookup_table
+            .apply(layer.lookup_table.map(Some), &source);
+        self.prune.apply(layer.prune, &source);
+        self.name.apply(layer.name, &source);
+        self.dir.apply(layer.dir, &source);
+        self.include.apply(layer.include, &source);
+        self.exclude.apply(layer.exclude, &source);
+        self.allow_secrets.apply(layer.allow_secrets, &source);
+    }
+
+    /// Client built from the effective values, decrypting keystores as needed.
+    pub fn client(&self) -> W3Result<W3Client> {
+        let signer = match self.signer.value.as_str() {
+            "" => resolve_signer("")?,
+            source => signer_from_source(source)?,
+        };
+        let mut client = self.client_with(signer)?;
+        client.payer = match self.payer.value.as_deref() {
+            Some(source) if !source.is_empty() => Some(signer_from_source(source)?),
+            _ => None,
+        };
+        Ok(client)
+    }
+
+    /// Client for commands that only read the chain, no key is loaded.
+    pub fn read_only_client(&self) -> W3Result<W3Client> {
+        self.client_with(Box::new(Keypair::new()))
+    }
+
+    fn client_with(&self, signer: BoxedSigner) -> W3Result<W3Client> {
+        let program = Pubkey::from_str(&self.program.value).map_err(|e| {
+            W3ClientError::Config(format!(
+                "program {} from {}: {}",
+                self.program.value, self.program.source, e
+            ))
+        })?;
+        let network = Network::from_string(&self.network.value);
+        let mut client = W3Client::new(program, signer, network, self.trunk_size.value);
+        client.dedup = self.dedup.value;
+        client.in_flight = self.in_flight.value;
+        client.retry.retries = self.retries.value;
+        client.fees = FeePolicy::new(self.priority_fee.value, self.max_priority_spend.value);
+        client.filter = FileFilter::new(&self.include.value, &self.exclude.value)?;
+        client.allow_secrets = parse_globs(&self.allow_secrets.value)?;
+        client.lookup_table = match self.lookup_table.value.as_deref() {
+            Some(table) if !table.is_empty() => Some(Pubkey::from_str(table).map_err(|e| {
+                W3ClientError::Config(format!(
+                    "lookup table {} from {}: {}",
+                    table, self.lookup_table.source, e
+                ))
+            })?),
+            _ => None,
+        };
+        Ok(client)
+    }
+
+    /// Prints every effective value and where it comes from, private keys
+    /// are never shown.
+    pub fn print(&self) {
+        let payer = match self.payer.value.as_deref() {
+            Some(source) if !source.is_empty() => redact_signer(source),
+            _ => "signer".to_string(),
+        };
+        let rows = [
+            ("program", self.program.value.clone(), &self.program.source),
+            ("network", self.network.value.clone(), &self.network.source),
+            (
+                "signer",
+                redact_signer(&self.signer.value),
+                &self.signer.source,
+            ),
+            ("payer", payer, &self.payer.source),
+            (
+                "trunk_size",
+                self.trunk_size.value.to_string(),
+                &self.trunk_size.source,
+            ),
+            ("dedup", self.dedup.value.to_string(), &self.dedup.source),
+            (
+                "in_flight",
+                self.in_flight.value.to_string(),
+                &self.in_flight.source,
+            ),
+            (
+                "retries",
+                self.retries.value.to_string(),
+                &self.retries.source,
+            ),
+            (
+                "priority_fee",
+                self.priority_fee.value.to_string(),
+                &self.priority_fee.source,
+            ),
+            (
+                "max_priority_spend",
+                self.max_priority_spend
+                    .value
+                    .map_or("unlimited".to_string(), |max| format!("{} lamports", max)),
+                &self.max_priority_spend.source,
+            ),
+            (
+                "lookup_table",
+                self.lookup_table
+                    .value
+                    .clone()
+                    .filter(|table| !table.is_empty())
+                    .unwrap_or_else(|| "none".to_string()),
+                &self.lookup_table.source,
+            ),
+            ("prune", self.prune.value.to_string(), &self.prune.source),
+            ("name", self.name.value.clone(), &self.name.source),
+            (
+                "dir",
+                self.dir.value.display().to_string(),
+                &self.dir.source,
+            ),
+            (
+                "include",
+                self.include.value.join(","),
+                &self.include.source,
+            ),
+            (
+                "exclude",
+                self.exclude.value.join(","),
+                &self.exclude.source,
+            ),
+            (
+                "allow_secrets",
+                self.allow_secrets.value.join(","),
+                &self.allow_secrets.source,
+            ),
+        ];
+        if let Some(profile) = &self.profile {
+            println!("{:<18} {}", "profile", profile);
+        }
+        for (key, value, source) in rows {
+            println!("{:<18} {:<46} ({})", key, value, source);
+        }
+    }
+}
+
+/// Shows signer sources naming a file or command, hides inline keys.
+fn redact_signer(source: &str) -> String {
+    let path = crate::signer::expand_home(source);
+    if source.is_empty() {
+        "solana cli keypair".to_string()
+    } else if source.starts_with("external:") || path.is_file() {
+        source.to_string()
+    } else {
+        "<base58 key>".to_string()
+    }
+}
+
+fn relative_to(dir: &Path, source: String) -> String {
+    let path = dir.join(&source);
+    if Path::new(&source).is_relative() && path.is_file() {
+        path.to_string_lossy().into_owned()
+    } else {
+        source
+    }
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("w3-uploader").join("config.yaml"))
+}
+
+/// First `w3-uploader.yaml` in `dir` or one of its parents.
+pub fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG))
+        .find(|path| path.is_file())
+}
@@ -0,0 +1,223 @@
+use {
+    log::{error, info, warn},
+    solana_sdk::instruction::Instruction,
+    std::{
+        collections::{BTreeMap, VecDeque},
+        sync::{Condvar, Mutex},
+        thread,
+    },
+};
+
+use crate::{client::W3Client, journal::UploadJournal, solana_trait::SolanaTransaction};
+
+/// Transactions uploading one file. Transactions of a stage may land in any
+/// order, a stage only starts once every transaction of the previous one is
+/// confirmed, e.g. the last trunk of a page or the link of a blob.
+pub struct UploadJob {
+    /// Web paths written by the job, several for a batch of small files.
+    pub paths: Vec<String>,
+    pub stages: Vec<Vec<Vec<Instruction>>>,
+}
+
+impl UploadJob {
+    pub fn new(web_path: &str) -> Self {
+        Self::batch(vec![web_path.to_string()])
+    }
+
+    pub fn batch(paths: Vec<String>) -> Self {
+        Self {
+            paths,
+            stages: Vec::new(),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        self.paths.join(", ")
+    }
+
+    pub fn stage(mut self, transactions: Vec<Vec<Instruction>>) -> Self {
+        if !transactions.is_empty() {
+            self.stages.push(transactions);
+        }
+        self
+    }
+
+    pub fn transactions(&self) -> usize {
+        self.stages.iter().map(|stage| stage.len()).sum()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct UploadReport {
+    pub succeeded: Vec<String>,
+    pub failed: Vec<String>,
+    /// Last error of each failed path.
+    pub errors: BTreeMap<String, String>,
+}
+
+struct JobState {
+    stage: usize,
+    pending: usize,
+    error: Option<String>,
+}
+
+struct EngineState {
+    queue: VecDeque<(usize, Vec<Instruction>)>,
+    jobs: Vec<JobState>,
+    active: usize,
+}
+
+/// Sends the transactions of many jobs with up to `in_flight` of them
+/// awaiting confirmation at the same time.
+pub struct UploadEngine<'a> {
+    client: &'a W3Client,
+    in_flight: usize,
+    journal: Option<&'a UploadJournal>,
+}
+
+impl<'a> UploadEngine<'a> {
+    pub fn new(client: &'a W3Client, in_flight: usize) -> Self {
+        Self {
+            client,
+            in_flight: in_flight.max(1),
+            journal: None,
+        }
+    }
+
+    /// Skips the transactions already confirmed in `journal` and records the
+    /// ones confirmed by this run.
+    pub fn journal(mut self, journal: &'a UploadJournal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    pub fn run(&self, jobs: Vec<UploadJob>) -> UploadReport {
+        let total: usize = jobs.iter().map(UploadJob::transactions).sum();
+        info!(
+            "Uploading {} files in {} transactions, {} in flight",
+            jobs.len(),
+            total,
+            self.in_flight
+        );
+
+        let mut state = EngineState {
+            queue: VecDeque::new(),
+            jobs: jobs
+                .iter()
+                .map(|_| JobState {
+                    stage: 0,
+                    pending: 0,
+                    error: None,
+                })
+                .collect(),
+            active: jobs.len(),
+        };
+        for idx in 0..jobs.len() {
+            self.start_stage(&jobs, &mut state, idx);
+        }
+
+        let state = Mutex::new(state);
+        let wakeup = Condvar::new();
+        thread::scope(|scope| {
+            for _ in 0..self.in_flight {
+                scope.spawn(|| self.worker(&jobs, &state, &wakeup));
+            }
+        });
+
+        let state = state.into_inner().unwrap();
+        let mut report = UploadReport::default();
+        for (job, job_state) in jobs.into_iter().zip(state.jobs) {
+            match job_state.error {
+                Some(error) => {
+                    for web_path in &job.paths {
+                        report.errors.insert(web_path.clone(), error.clone());
+                    }
+                    report.failed.extend(job.paths);
+                }
+                None => report.succeeded.extend(job.paths),
+            }
+        }
+        report
+    }
+
+    /// Queues the transactions of the current stage of a job, skipping empty
+    /// or journaled stages and retiring the job when it has none left.
+    fn start_stage(&self, jobs: &[UploadJob], state: &mut EngineState, idx: usize) {
+        let job = &jobs[idx];
+        while state.jobs[idx].stage < job.stages.len() {
+            let transactions: Vec<&Vec<Instruction>> = job.stages[state.jobs[idx].stage]
+                .iter()
+                .filter(|instructions| {
+                    let confirmed = self
+                        .journal
+                        .is_some_and(|journal| journal.is_confirmed(instructions));
+                    if confirmed {
+                        info!("{} transaction already confirmed", job.label());
+                    }
+                    !confirmed
+                })
+                .collect();
+            if !transactions.is_empty() {
+                state.jobs[idx].pending = transactions.len();
+                for instructions in transactions {
+                    state.queue.push_back((idx, instructions.clone()));
+                }
+                return;
+            }
+            state.jobs[idx].stage += 1;
+        }
+        state.active -= 1;
+    }
+
+    fn worker(&self, jobs: &[UploadJob], state: &Mutex<EngineState>, wakeup: &Condvar) {
+        let payer = self.client.fee_payer().pubkey();
+        let signers = self.client.signers();
+        loop {
+            let (idx, instructions) = {
+                let mut guard = state.lock().unwrap();
+                loop {
+                    if let Some(task) = guard.queue.pop_front() {
+                        break task;
+                    }
+                    if guard.active == 0 {
+                        return;
+                    }
+                    guard = wakeup.wait(guard).unwrap();
+                }
+            };
+
+            let result = self
+                .client
+                .send_transaction(&payer, &signers, &instructions);
+
+            let mut guard = state.lock().unwrap();
+            match result {
+                Ok(signature) => {
+                    info!("{} confirmed {}", jobs[idx].label(), signature);
+                    if let Some(journal) = self.journal {
+                        if let Err(e) =
+                            journal.record(&jobs[idx].label(), &instructions, &signature)
+                        {
+                            warn!("Cannot write upload journal : {:?}", e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("{} transaction failed : {:?}", jobs[idx].label(), e);
+                    guard.jobs[idx].error = Some(e.to_string());
+                }
+            }
+            guard.jobs[idx].pending -= 1;
+            if guard.jobs[idx].pending == 0 {
+                if guard.jobs[idx].error.is_some() {
+                    // later stages rely on this one, do not send them
+                    guard.active -= 1;
+                } else {
+                    guard.jobs[idx].stage += 1;
+                    self.start_stage(jobs, &mut guard, idx);
+                }
+            }
+            wakeup.notify_all();
+        }
+    }
+}
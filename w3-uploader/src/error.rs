@@ -0,0 +1,52 @@
+use {
+    solana_client::client_error::ClientError,
+    solana_sdk::{signer::SignerError, transaction::TransactionError},
+    thiserror::Error,
+};
+
+/// Errors returned by the public API of the uploader.
+#[derive(Debug, Error)]
+pub enum W3ClientError {
+    /// Unreadable or incomplete client configuration.
+    #[error("config error: {0}")]
+    Config(String),
+    #[error("invalid key: {0}")]
+    Key(String),
+    #[error("signer error: {0}")]
+    Signer(#[from] SignerError),
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    /// The RPC node could not be reached or refused the request. Boxed as
+    /// `ClientError` is large.
+    #[error("rpc error: {0}")]
+    Rpc(Box<ClientError>),
+    /// The cluster or the program rejected a transaction.
+    #[error("transaction error: {0}")]
+    Transaction(TransactionError),
+    /// Files about to be uploaded that look like they hold credentials.
+    #[error("possible secrets in {0}")]
+    Secret(String),
+    /// Account data that does not match its expected layout.
+    #[error("decode error: {0}")]
+    Decode(String),
+    /// A page that could not be written, with the last error of its upload.
+    #[error("upload failed: {0}")]
+    Upload(String),
+}
+
+impl From<ClientError> for W3ClientError {
+    fn from(e: ClientError) -> Self {
+        match e.get_transaction_error() {
+            Some(e) => Self::Transaction(e),
+            None => Self::Rpc(Box::new(e)),
+        }
+    }
+}
+
+pub type W3Result<T> = Result<T, W3ClientError>;
@@ -0,0 +1,260 @@
+//! Priority fees and compute-unit limits of the transactions sent.
+//!
+//! With a compute-unit price set, every transaction is simulated first and
+//! sent with a limit just above the units it consumed, so the priority fee
+//! is paid for the units actually used. The price is fixed, or estimated
+//! from the fees recently paid to write the same accounts. Once the priority
+//! fees of a run would exceed the spend cap, transactions go out without
+//! one.
+
+use {
+    log::{debug, warn},
+    serde::{de, Deserialize, Deserializer},
+    solana_client::rpc_config::RpcSimulateTransactionConfig,
+    solana_sdk::{
+        compute_budget::ComputeBudgetInstruction, hash::Hash, instruction::Instruction,
+        pubkey::Pubkey, signature::Signature, system_program, transaction::VersionedTransaction,
+    },
+    std::{fmt, str::FromStr, sync::Mutex},
+};
+
+use crate::{
+    client::W3Client,
+    error::{W3ClientError, W3Result},
+    packer,
+    solana_trait::compile_message,
+};
+
+/// Most compute units a transaction may request.
+pub const MAX_COMPUTE_UNITS: u32 = 1_400_000;
+const MICRO_LAMPORTS_PER_LAMPORT: u64 = 1_000_000;
+/// Percentile of the recent prioritization fees an estimated price matches.
+const ESTIMATE_PERCENTILE: usize = 75;
+
+/// Compute-unit price of a transaction, in micro-lamports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PriorityFee {
+    /// No compute budget instructions, the transaction as built.
+    #[default]
+    None,
+    Fixed(u64),
+    /// Estimated from `getRecentPrioritizationFees`.
+    Auto,
+}
+
+impl FromStr for PriorityFee {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(Self::None),
+            "auto" => Ok(Self::Auto),
+            price => price
+                .parse()
+                .map(Self::Fixed)
+                .map_err(|_| format!("{:?} is not a price, auto or none", price)),
+        }
+    }
+}
+
+impl fmt::Display for PriorityFee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Fixed(price) => write!(f, "{} micro-lamports/CU", price),
+            Self::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+/// Accepts `auto`, `none` or a number of micro-lamports, quoted or not.
+impl<'de> Deserialize<'de> for PriorityFee {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = PriorityFee;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a price in micro-lamports, auto or none")
+            }
+
+            fn visit_u64<E: de::Error>(self, price: u64) -> Result<PriorityFee, E> {
+                Ok(PriorityFee::Fixed(price))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<PriorityFee, E> {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Spending {
+    spent: u64,
+    /// A fee was dropped to stay under the cap.
+    capped: bool,
+}
+
+/// Priority fee settings and spending of a client.
+#[derive(Debug, Default)]
+pub struct FeePolicy {
+    pub price: PriorityFee,
+    /// Most lamports spent on priority fees, unlimited when `None`.
+    pub max_spend: Option<u64>,
+    spending: Mutex<Spending>,
+}
+
+impl FeePolicy {
+    pub fn new(price: PriorityFee, max_spend: Option<u64>) -> Self {
+        Self {
+            price,
+            max_spend,
+            spending: Mutex::default(),
+        }
+    }
+
+    /// Lamports of priority fees paid so far.
+    pub fn spent(&self) -> u64 {
+        self.spending.lock().unwrap().spent
+    }
+
+    /// Reserves the fee of `units` at `price`, returns the price to pay,
+    /// zero once the cap is reached.
+    pub fn reserve(&self, units: u32, price: u64) -> u64 {
+        let fee = priority_fee(units, price);
+        let mut spending = self.spending.lock().unwrap();
+        match self.max_spend {
+            Some(max) if spending.spent + fee > max => {
+                if !spending.capped {
+                    warn!(
+                        "Priority fees reached the cap of {} lamports, sending without",
+                        max
+                    );
+                    spending.capped = true;
+                }
+                0
+            }
+            _ => {
+                spending.spent += fee;
+                price
+            }
+        }
+    }
+
+    /// Bytes the compute budget instructions add to each transaction.
+    pub fn reserved_size(&self) -> usize {
+        match self.price {
+            PriorityFee::None => 0,
+            _ => {
+                let payer = Pubkey::default();
+                let probe = Instruction::new_with_bytes(system_program::ID, &[], Vec::new());
+                let mut budgeted = budget_instructions(MAX_COMPUTE_UNITS, u64::MAX);
+                budgeted.push(probe.clone());
+                packer::transaction_size(&budgeted, &payer)
+                    - packer::transaction_size(&[probe], &payer)
+            }
+        }
+    }
+}
+
+/// Lamports paid for `units` at `price` micro-lamports, rounded up.
+pub fn priority_fee(units: u32, price: u64) -> u64 {
+    let micro_lamports = units as u128 * price as u128;
+    micro_lamports.div_ceil(MICRO_LAMPORTS_PER_LAMPORT as u128) as u64
+}
+
+pub fn budget_instructions(units: u32, price: u64) -> Vec<Instruction> {
+    vec![
+        ComputeBudgetInstruction::set_compute_unit_limit(units),
+        ComputeBudgetInstruction::set_compute_unit_price(price),
+    ]
+}
+
+/// Price at the `ESTIMATE_PERCENTILE` of `fees`, zero without any.
+pub fn fee_percentile(mut fees: Vec<u64>) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    fees.sort_unstable();
+    fees[(fees.len() - 1) * ESTIMATE_PERCENTILE / 100]
+}
+
+impl W3Client {
+    /// `instructions` preceded by the compute budget instructions of the
+    /// fee policy.
+    pub fn with_compute_budget(
+        &self,
+        payer: &Pubkey,
+        instructions: &[Instruction],
+    ) -> W3Result<Vec<Instruction>> {
+        let price = match self.fees.price {
+            PriorityFee::None => return Ok(instructions.to_vec()),
+            PriorityFee::Fixed(price) => price,
+            PriorityFee::Auto => self.estimate_price(instructions)?,
+        };
+        let units = self.simulate_units(payer, price, instructions)?;
+        let price = self.fees.reserve(units, price);
+        let mut budgeted = budget_instructions(units, price);
+        budgeted.extend_from_slice(instructions);
+        Ok(budgeted)
+    }
+
+    /// Recent price of writing the accounts of `instructions`.
+    fn estimate_price(&self, instructions: &[Instruction]) -> W3Result<u64> {
+        let mut writable: Vec<Pubkey> = instructions
+            .iter()
+            .flat_map(|instruction| &instruction.accounts)
+            .filter(|meta| meta.is_writable)
+            .map(|meta| meta.pubkey)
+            .collect();
+        writable.sort();
+        writable.dedup();
+        let fees = self.connection.get_recent_prioritization_fees(&writable)?;
+        let price = fee_percentile(fees.iter().map(|fee| fee.prioritization_fee).collect());
+        debug!("Estimated priority fee: {} micro-lamports/CU", price);
+        Ok(price)
+    }
+
+    /// Compute units of `instructions` with a margin, from a simulation.
+    fn simulate_units(
+        &self,
+        payer: &Pubkey,
+        price: u64,
+        instructions: &[Instruction],
+    ) -> W3Result<u32> {
+        let mut simulated = budget_instructions(MAX_COMPUTE_UNITS, price);
+        simulated.extend_from_slice(instructions);
+        // encoded as it will be sent, the blockhash is replaced by the node
+        let message = compile_message(payer, &simulated, &self.lookup_tables, Hash::default())?;
+        let transaction = VersionedTransaction {
+            signatures: vec![
+                Signature::default();
+                message.header().num_required_signatures as usize
+            ],
+            message,
+        };
+        let result = self
+            .connection
+            .simulate_transaction_with_config(
+                &transaction,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(self.connection.commitment()),
+                    ..RpcSimulateTransactionConfig::default()
+                },
+            )?
+            .value;
+        if let Some(e) = result.err {
+            return Err(W3ClientError::Transaction(e));
+        }
+        let units = result.units_consumed.unwrap_or(MAX_COMPUTE_UNITS as u64);
+        // units vary slightly with the state the transaction lands on
+        let limit = units + units / 10;
+        Ok(limit.min(MAX_COMPUTE_UNITS as u64) as u32)
+    }
+}
@@ -0,0 +1,197 @@
+//! Selection of the files to upload.
+//!
+//! `.w3ignore` files follow gitignore semantics: a pattern without a slash
+//! matches a name at any depth, one with a slash is relative to the
+//! directory of the ignore file, a trailing slash matches directories only,
+//! `!` re-includes and the last matching pattern wins. Files inside an
+//! ignored directory cannot be re-included.
+//!
+//! The `include` and `exclude` globs of the config use the same syntax,
+//! relative to the upload directory. Excluded paths are always skipped, when
+//! any include is given a file must match one, directly or through one of
+//! its directories.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use globset::{GlobBuilder, GlobMatcher};
+
+use crate::error::{W3ClientError, W3Result};
+
+pub const IGNORE_FILE: &str = ".w3ignore";
+
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    glob: GlobMatcher,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the whole relative path instead of the name.
+    anchored: bool,
+}
+
+impl Pattern {
+    /// Parses a line of an ignore file, `None` for blank lines, comments and
+    /// malformed globs.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        if line.is_empty() {
+            return None;
+        }
+        // `*`, `?` and classes stay within a name, `**/` spans directories
+        let glob = GlobBuilder::new(line)
+            .literal_separator(true)
+            .backslash_escape(true)
+            .build()
+            .ok()?
+            .compile_matcher();
+        Some(Self {
+            glob,
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    /// Whether the pattern matches `path`, relative to its base with `/`
+    /// separators.
+    pub fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let text = match self.anchored {
+            true => path,
+            false => path.rsplit('/').next().unwrap_or(path),
+        };
+        self.glob.is_match(text)
+    }
+}
+
+/// Parses globs given in the config or on the command line, negation is
+/// only meaningful in ignore files.
+pub fn parse_globs(globs: &[String]) -> W3Result<Vec<Pattern>> {
+    globs
+        .iter()
+        .map(|glob| match Pattern::parse(glob) {
+            Some(pattern) if !pattern.negated => Ok(pattern),
+            _ => Err(W3ClientError::Config(format!("bad glob {:?}", glob))),
+        })
+        .collect()
+}
+
+/// Include and exclude globs relative to the upload directory.
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl FileFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> W3Result<Self> {
+        Ok(Self {
+            include: parse_globs(include)?,
+            exclude: parse_globs(exclude)?,
+        })
+    }
+
+    /// Whether the walk enters the directory at `relative`.
+    pub fn enters(&self, relative: &str) -> bool {
+        !self
+            .exclude
+            .iter()
+            .any(|pattern| pattern.matches(relative, true))
+    }
+
+    /// Whether the file at `relative` is uploaded.
+    pub fn selects(&self, relative: &str) -> bool {
+        if self
+            .exclude
+            .iter()
+            .any(|pattern| pattern.matches(relative, false))
+        {
+            return false;
+        }
+        if self.include.is_empty() {
+            return true;
+        }
+        // the file itself, then each of its directories
+        let mut candidates = vec![(relative, false)];
+        candidates.extend(
+            relative
+                .match_indices('/')
+                .map(|(i, _)| (&relative[..i], true)),
+        );
+        candidates.into_iter().any(|(path, is_dir)| {
+            self.include
+                .iter()
+                .any(|pattern| pattern.matches(path, is_dir))
+        })
+    }
+}
+
+/// Patterns of one ignore file with the directory they are relative to.
+#[derive(Debug)]
+struct IgnoreFile {
+    base: PathBuf,
+    patterns: Vec<Pattern>,
+}
+
+/// Ignore files of the directories entered by a walk, outermost first.
+#[derive(Debug, Default)]
+pub struct IgnoreStack {
+    files: Vec<IgnoreFile>,
+}
+
+impl IgnoreStack {
+    /// Enters `dir`, reading its ignore file. Returns whether one was found,
+    /// which `leave` must then drop.
+    pub fn enter(&mut self, dir: &Path) -> W3Result<bool> {
+        let path = dir.join(IGNORE_FILE);
+        if !path.is_file() {
+            return Ok(false);
+        }
+        let patterns = fs::read_to_string(&path)?
+            .lines()
+            .filter_map(Pattern::parse)
+            .collect();
+        self.files.push(IgnoreFile {
+            base: dir.to_path_buf(),
+            patterns,
+        });
+        Ok(true)
+    }
+
+    pub fn leave(&mut self) {
+        self.files.pop();
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut ignored = false;
+        for file in &self.files {
+            let relative = match path.strip_prefix(&file.base) {
+                Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+                Err(_) => continue,
+            };
+            for pattern in &file.patterns {
+                if pattern.matches(&relative, is_dir) {
+                    ignored = !pattern.negated;
+                }
+            }
+        }
+        ignored
+    }
+}
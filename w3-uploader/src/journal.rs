@@ -0,0 +1,230 @@
+use {
+    log::{info, warn},
+    serde::{Deserialize, Serialize},
+    solana_sdk::{
+        hash::{hashv, Hash},
+        instruction::Instruction,
+        pubkey::Pubkey,
+        signature::Signature,
+    },
+    std::{
+        collections::{BTreeMap, BTreeSet, HashMap},
+        fs,
+        path::{Path, PathBuf},
+        str::FromStr,
+        sync::Mutex,
+    },
+};
+
+use crate::{
+    client::W3Client,
+    error::{W3ClientError, W3Result},
+    plan::written_accounts,
+    reader::MAX_MULTIPLE_ACCOUNTS,
+};
+
+/// Journal file kept in the uploaded directory, dotfiles are never uploaded.
+pub const JOURNAL_FILE: &str = ".w3-upload-state.json";
+
+/// `getSignatureStatuses` accepts at most 256 signatures per call.
+const STATUS_BATCH: usize = 256;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalEntry {
+    pub path: String,
+    pub signature: String,
+    /// Data size the transaction gave each account it wrote.
+    #[serde(default)]
+    pub accounts: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct JournalFile {
+    /// Confirmed transactions keyed by the digest of their instructions.
+    transactions: BTreeMap<String, JournalEntry>,
+}
+
+impl JournalFile {
+    /// Drops the entries whose accounts no longer have the size they were
+    /// written with, `sizes` holding the data size of the existing accounts.
+    fn retain_written(&mut self, sizes: &HashMap<String, usize>) {
+        self.transactions.retain(|_, entry| {
+            let written = entry
+                .accounts
+                .iter()
+                .all(|(account, size)| sizes.get(account) == Some(size));
+            if !written {
+                warn!(
+                    "{} accounts written by {} changed since",
+                    entry.path, entry.signature
+                );
+            }
+            written
+        });
+    }
+}
+
+/// Confirmed upload transactions persisted as they land, so an interrupted
+/// upload resumes without sending them again.
+pub struct UploadJournal {
+    path: PathBuf,
+    state: Mutex<JournalFile>,
+}
+
+/// Identifies a transaction by its instructions, which do not depend on the
+/// blockhash it was signed with.
+pub fn transaction_digest(instructions: &[Instruction]) -> Hash {
+    let mut parts: Vec<Vec<u8>> = Vec::new();
+    for instruction in instructions {
+        parts.push(instruction.program_id.to_bytes().to_vec());
+        for meta in &instruction.accounts {
+            let mut account = meta.pubkey.to_bytes().to_vec();
+            account.push(meta.is_signer as u8);
+            account.push(meta.is_writable as u8);
+            parts.push(account);
+        }
+        parts.push(instruction.data.clone());
+    }
+    let parts: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+    hashv(&parts)
+}
+
+impl UploadJournal {
+    /// Opens the journal of `dir`, starting empty when there is none.
+    pub fn open(dir: &Path) -> W3Result<Self> {
+        let path = dir.join(JOURNAL_FILE);
+        let state = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|e| W3ClientError::Config(format!("{}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => JournalFile::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops the entries whose transaction cannot be found on chain or whose
+    /// accounts were since resized or closed, they are sent again.
+    pub fn verify(&self, client: &W3Client) -> W3Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let entries: Vec<(String, JournalEntry)> = state
+            .transactions
+            .iter()
+            .map(|(digest, entry)| (digest.clone(), entry.clone()))
+            .collect();
+        for batch in entries.chunks(STATUS_BATCH) {
+            let signatures: Vec<Signature> = batch
+                .iter()
+                .map(|(_, entry)| Signature::from_str(&entry.signature).unwrap_or_default())
+                .collect();
+            let statuses = match client
+                .connection
+                .get_signature_statuses_with_history(&signatures)
+            {
+                Ok(statuses) => statuses.value,
+                Err(e) => {
+                    warn!("Cannot verify journal, resending its transactions: {:?}", e);
+                    vec![None; batch.len()]
+                }
+            };
+            for ((digest, entry), status) in batch.iter().zip(statuses) {
+                if !matches!(status, Some(status) if status.err.is_none()) {
+                    warn!(
+                        "{} transaction {} did not land",
+                        entry.path, entry.signature
+                    );
+                    state.transactions.remove(digest);
+                }
+            }
+        }
+
+        let accounts: BTreeSet<Pubkey> = state
+            .transactions
+            .values()
+            .flat_map(|entry| entry.accounts.keys())
+            .filter_map(|account| Pubkey::from_str(account).ok())
+            .collect();
+        let accounts: Vec<Pubkey> = accounts.into_iter().collect();
+        let mut sizes = HashMap::new();
+        for chunk in accounts.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            match client.connection.get_multiple_accounts(chunk) {
+                Ok(found) => {
+                    for (key, account) in chunk.iter().zip(found) {
+                        if let Some(account) = account {
+                            sizes.insert(key.to_string(), account.data.len());
+                        }
+                    }
+                }
+                Err(e) => warn!("Cannot read journaled accounts, resending: {:?}", e),
+            }
+        }
+        state.retain_written(&sizes);
+
+        info!(
+            "Journal {} holds {} confirmed transactions",
+            self.path.display(),
+            state.transactions.len()
+        );
+        Ok(())
+    }
+
+    /// `verify` against the data size of the existing accounts.
+    pub fn retain_written(&self, sizes: &HashMap<String, usize>) {
+        self.state.lock().unwrap().retain_written(sizes);
+    }
+
+    pub fn is_confirmed(&self, instructions: &[Instruction]) -> bool {
+        let digest = transaction_digest(instructions).to_string();
+        self.state
+            .lock()
+            .unwrap()
+            .transactions
+            .contains_key(&digest)
+    }
+
+    /// Records a confirmed transaction and writes the journal to disk.
+    pub fn record(
+        &self,
+        path: &str,
+        instructions: &[Instruction],
+        signature: &Signature,
+    ) -> W3Result<()> {
+        let mut accounts = BTreeMap::new();
+        for (account, size) in instructions.iter().flat_map(written_accounts) {
+            let entry = accounts.entry(account.to_string()).or_default();
+            *entry = size.max(*entry);
+        }
+        let mut state = self.state.lock().unwrap();
+        state.transactions.insert(
+            transaction_digest(instructions).to_string(),
+            JournalEntry {
+                path: path.to_string(),
+                signature: signature.to_string(),
+                accounts,
+            },
+        );
+        // write aside then rename so a crash never leaves a truncated journal
+        let data = serde_json::to_vec_pretty(&*state)?;
+        let temp = self.path.with_extension("json.tmp");
+        fs::write(&temp, data)?;
+        Ok(fs::rename(&temp, &self.path)?)
+    }
+
+    /// Deletes the journal once every upload succeeded.
+    pub fn finish(self) -> W3Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,295 @@
+//! Passphrase encrypted keystore for signer keys.
+//!
+//! The keypair is sealed with AES-256-GCM-SIV under a key derived from the
+//! passphrase with scrypt (RFC 7914). The pubkey is stored in clear and
+//! authenticated as associated data so a keystore can be identified without
+//! its passphrase.
+//!
+//! On the command line the passphrase is read from `W3_KEYSTORE_PASSPHRASE`,
+//! or asked on the terminal.
+
+use {
+    aes_gcm_siv::{
+        aead::{Aead, NewAead, Payload},
+        Aes256GcmSiv, Key, Nonce,
+    },
+    base64::{engine::general_purpose::STANDARD, Engine},
+    rand::{rngs::OsRng, RngCore},
+    serde::{Deserialize, Serialize},
+    solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+    },
+    std::{env, fs, path::Path, str::FromStr},
+    zeroize::{Zeroize, Zeroizing},
+};
+
+use crate::error::{W3ClientError, W3Result};
+
+pub const PASSPHRASE_ENV: &str = "W3_KEYSTORE_PASSPHRASE";
+pub const NEW_PASSPHRASE_ENV: &str = "W3_KEYSTORE_NEW_PASSPHRASE";
+
+const VERSION: u8 = 1;
+const CIPHER: &str = "aes-256-gcm-siv";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+/// Largest scrypt working memory accepted from a keystore file, 32 times the
+/// defaults.
+const MAX_KDF_MEMORY: u64 = 1 << 30;
+
+/// scrypt cost, `N = 2^log_n`. The defaults take 32 MiB and a fraction of a
+/// second.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScryptParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+    pub salt: String,
+}
+
+impl ScryptParams {
+    pub fn new(log_n: u8) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            log_n,
+            r: 8,
+            p: 1,
+            salt: STANDARD.encode(salt),
+        }
+    }
+
+    /// Bytes scrypt allocates: `N` blocks of `128 * r` bytes, and `p` more.
+    fn memory(&self) -> Option<u64> {
+        1u64.checked_shl(self.log_n.into())
+            .and_then(|n| n.checked_add(self.p.into()))
+            .and_then(|blocks| blocks.checked_mul(128 * u64::from(self.r)))
+    }
+
+    fn derive_key(&self, passphrase: &str) -> W3Result<[u8; KEY_LEN]> {
+        let bounded = self.memory().is_some_and(|memory| memory <= MAX_KDF_MEMORY);
+        if self.log_n == 0 || self.r == 0 || self.p == 0 || !bounded {
+            return Err(W3ClientError::Key(format!(
+                "unsupported scrypt parameters log_n={} r={} p={}",
+                self.log_n, self.r, self.p
+            )));
+        }
+        let salt = decode_field("salt", &self.salt)?;
+        let mut key = [0u8; KEY_LEN];
+        scrypt(
+            passphrase.as_bytes(),
+            &salt,
+            self.log_n,
+            self.r,
+            self.p,
+            &mut key,
+        )?;
+        Ok(key)
+    }
+}
+
+impl Default for ScryptParams {
+    fn default() -> Self {
+        Self::new(15)
+    }
+}
+
+/// On disk format of an encrypted keypair.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Keystore {
+    pub version: u8,
+    pub pubkey: String,
+    pub kdf: ScryptParams,
+    pub cipher: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+impl Keystore {
+    pub fn encrypt(keypair: &Keypair, passphrase: &str, kdf: ScryptParams) -> W3Result<Self> {
+        let pubkey = keypair.pubkey();
+        let mut key = kdf.derive_key(passphrase)?;
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let mut plaintext = keypair.to_bytes();
+        let sealed = Aes256GcmSiv::new(&Key::from(key)).encrypt(
+            &Nonce::from(nonce),
+            Payload {
+                msg: &plaintext,
+                aad: pubkey.as_ref(),
+            },
+        );
+        key.zeroize();
+        plaintext.zeroize();
+        let ciphertext = sealed.map_err(|_| W3ClientError::Key("cannot seal keystore".into()))?;
+        Ok(Self {
+            version: VERSION,
+            pubkey: pubkey.to_string(),
+            kdf,
+            cipher: CIPHER.to_string(),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        })
+    }
+
+    pub fn decrypt(&self, passphrase: &str) -> W3Result<Keypair> {
+        if self.version != VERSION || self.cipher != CIPHER {
+            return Err(W3ClientError::Key(format!(
+                "unsupported keystore version {} with cipher {}",
+                self.version, self.cipher
+            )));
+        }
+        let pubkey = Pubkey::from_str(&self.pubkey)
+            .map_err(|e| W3ClientError::Key(format!("keystore pubkey: {}", e)))?;
+        let nonce: [u8; NONCE_LEN] = decode_field("nonce", &self.nonce)?
+            .try_into()
+            .map_err(|_| W3ClientError::Key("keystore nonce must be 12 bytes".into()))?;
+        let ciphertext = decode_field("ciphertext", &self.ciphertext)?;
+        let mut key = self.kdf.derive_key(passphrase)?;
+        let opened = Aes256GcmSiv::new(&Key::from(key)).decrypt(
+            &Nonce::from(nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: pubkey.as_ref(),
+            },
+        );
+        key.zeroize();
+        let mut plaintext =
+            opened.map_err(|_| W3ClientError::Key("wrong keystore passphrase".into()))?;
+        let keypair = Keypair::from_bytes(&plaintext)
+            .map_err(|e| W3ClientError::Key(format!("keystore keypair: {}", e)));
+        plaintext.zeroize();
+        let keypair = keypair?;
+        if keypair.pubkey() != pubkey {
+            return Err(W3ClientError::Key(format!(
+                "keystore holds {} instead of {}",
+                keypair.pubkey(),
+                pubkey
+            )));
+        }
+        Ok(keypair)
+    }
+
+    /// Parses `data` when it looks like a keystore, Solana keypair files are
+    /// JSON arrays and never match.
+    pub fn from_slice(data: &[u8]) -> Option<W3Result<Self>> {
+        let first = data.iter().find(|byte| !byte.is_ascii_whitespace())?;
+        if *first != b'{' {
+            return None;
+        }
+        Some(serde_json::from_slice(data).map_err(|e| W3ClientError::Key(e.to_string())))
+    }
+
+    pub fn read(path: &Path) -> W3Result<Self> {
+        let data = fs::read(path)?;
+        Self::from_slice(&data)
+            .unwrap_or_else(|| Err(W3ClientError::Key("not a keystore".into())))
+            .map_err(|e| W3ClientError::Key(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Writes the keystore readable by the owner only.
+    pub fn write(&self, path: &Path) -> W3Result<()> {
+        let data = serde_json::to_vec_pretty(self)?;
+        let temp = path.with_extension("tmp");
+        write_private(&temp, &data)?;
+        Ok(fs::rename(&temp, path)?)
+    }
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, data: &[u8]) -> W3Result<()> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    Ok(file.write_all(data)?)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, data: &[u8]) -> W3Result<()> {
+    Ok(fs::write(path, data)?)
+}
+
+fn decode_field(name: &str, value: &str) -> W3Result<Vec<u8>> {
+    STANDARD
+        .decode(value)
+        .map_err(|e| W3ClientError::Key(format!("keystore {}: {}", name, e)))
+}
+
+/// Where keystore passphrases come from.
+#[derive(Clone)]
+pub enum PassphraseSource {
+    /// The environment variable of the passphrase when set, else the
+    /// terminal.
+    Terminal,
+    /// A passphrase handed over by the caller.
+    Given(Zeroizing<String>),
+}
+
+/// Passphrase unlocking the keystore at `path`.
+pub fn unlock_passphrase(path: &Path, source: &PassphraseSource) -> W3Result<Zeroizing<String>> {
+    match source {
+        PassphraseSource::Given(passphrase) => Ok(passphrase.clone()),
+        PassphraseSource::Terminal => match env::var(PASSPHRASE_ENV) {
+            Ok(passphrase) => Ok(Zeroizing::new(passphrase)),
+            Err(_) => Ok(Zeroizing::new(rpassword::prompt_password(format!(
+                "Passphrase for {}: ",
+                path.display()
+            ))?)),
+        },
+    }
+}
+
+/// Passphrase of a new keystore, from `env_name` on the terminal or typed
+/// twice.
+pub fn new_passphrase(env_name: &str, source: &PassphraseSource) -> W3Result<Zeroizing<String>> {
+    let passphrase = match source {
+        PassphraseSource::Given(passphrase) => passphrase.clone(),
+        PassphraseSource::Terminal => match env::var(env_name) {
+            Ok(passphrase) => Zeroizing::new(passphrase),
+            Err(_) => {
+                let first =
+                    Zeroizing::new(rpassword::prompt_password("New keystore passphrase: ")?);
+                let second = Zeroizing::new(rpassword::prompt_password("Repeat passphrase: ")?);
+                if first != second {
+                    return Err(W3ClientError::Key("passphrases do not match".into()));
+                }
+                first
+            }
+        },
+    };
+    if passphrase.is_empty() {
+        return Err(W3ClientError::Key("empty passphrase".into()));
+    }
+    Ok(passphrase)
+}
+
+/// Decrypts the keystore at `path`.
+pub fn open_keystore(path: &Path, source: &PassphraseSource) -> W3Result<Keypair> {
+    let keystore = Keystore::read(path)?;
+    keystore.decrypt(&unlock_passphrase(path, source)?)
+}
+
+/// scrypt key derivation (RFC 7914).
+pub fn scrypt(
+    passphrase: &[u8],
+    salt: &[u8],
+    log_n: u8,
+    r: u32,
+    p: u32,
+    out: &mut [u8],
+) -> W3Result<()> {
+    let params =
+        scrypt::Params::new(log_n, r, p, scrypt::Params::RECOMMENDED_LEN).map_err(|e| {
+            W3ClientError::Key(format!(
+                "unsupported scrypt parameters log_n={} r={} p={}: {}",
+                log_n, r, p, e
+            ))
+        })?;
+    scrypt::scrypt(passphrase, salt, &params, out)
+        .map_err(|e| W3ClientError::Key(format!("scrypt output: {}", e)))
+}
@@ -1,4 +1,23 @@
 pub mod client;
+pub mod config;
+pub mod engine;
+pub mod error;
+pub mod fees;
+pub mod filter;
+pub mod journal;
+pub mod keystore;
+pub mod listing;
+pub mod lookup;
+pub mod packer;
+pub mod plan;
+pub mod reader;
+pub mod schema;
 pub mod sdk;
+pub mod secrets;
+pub mod signer;
 pub mod solana_trait;
-pub mod test;
+pub mod sync;
+#[cfg(test)]
+mod test;
+pub mod verify;
+pub mod watch;
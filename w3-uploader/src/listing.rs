@@ -0,0 +1,286 @@
+//! Listing of the accounts holding a site.
+//!
+//! Page accounts are addressed by the hash of their web path, so paths are
+//! only known from the site manifest. Scanning every account of the program
+//! additionally finds names, blobs and accounts no manifest page refers to.
+
+use {
+    borsh::BorshDeserialize,
+    log::warn,
+    serde::Serialize,
+    solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config,
+    solana_sdk::{account::Account, hash::Hash, native_token::lamports_to_sol, pubkey::Pubkey},
+    std::{
+        collections::BTreeSet,
+        time::{Duration, UNIX_EPOCH},
+    },
+};
+
+use crate::{
+    client::W3Client,
+    error::{W3ClientError, W3Result},
+    reader::MAX_MULTIPLE_ACCOUNTS,
+    sdk::{BlobHeader, NameConfig, PageData},
+    sync::MANIFEST_PATH,
+};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    Name,
+    Raw,
+    Trunks,
+    Blob,
+    Account,
+    /// Program account of no known kind, e.g. a trunk of a deleted page.
+    Unknown,
+}
+
+impl EntryKind {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Raw => "raw",
+            Self::Trunks => "trunks",
+            Self::Blob => "blob",
+            Self::Account => "account",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ListEntry {
+    /// Web path of a page, the name of a name config, or the address of an
+    /// account found by scanning the program.
+    pub path: String,
+    pub kind: EntryKind,
+    /// Bytes of content served, the data length for names and unknown
+    /// accounts.
+    pub size: u64,
+    /// Lamports held by the accounts of the entry.
+    pub rent: u64,
+    pub accounts: Vec<String>,
+    /// Unix time of the last transaction touching the page, name or blob
+    /// account, only looked up when listing with `modified`.
+    pub last_modified: Option<i64>,
+}
+
+impl ListEntry {
+    fn new(path: String, kind: EntryKind, size: u64, accounts: &[(Pubkey, Account)]) -> Self {
+        Self {
+            path,
+            kind,
+            size,
+            rent: accounts.iter().map(|(_, account)| account.lamports).sum(),
+            accounts: accounts.iter().map(|(key, _)| key.to_string()).collect(),
+            last_modified: None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Listing {
+    pub entries: Vec<ListEntry>,
+    /// Lamports held by every listed account, blobs shared by several pages
+    /// counted once.
+    pub rent: u64,
+}
+
+impl Listing {
+    pub fn print(&self) {
+        println!(
+            "{:<8} {:>10} {:>14}  {:<20}  PATH",
+            "KIND", "SIZE", "RENT (SOL)", "MODIFIED"
+        );
+        for entry in &self.entries {
+            let modified = entry
+                .last_modified
+                .map(|time| {
+                    let time = UNIX_EPOCH + Duration::from_secs(time.max(0) as u64);
+                    humantime::format_rfc3339_seconds(time).to_string()
+                })
+                .unwrap_or_else(|| "-".into());
+            println!(
+                "{:<8} {:>10} {:>14.9}  {:<20}  {}",
+                entry.kind.label(),
+                entry.size,
+                lamports_to_sol(entry.rent),
+                modified,
+                entry.path
+            );
+        }
+        println!(
+            "{} entries, {} SOL held",
+            self.entries.len(),
+            lamports_to_sol(self.rent)
+        );
+    }
+}
+
+impl W3Client {
+    /// Lists the pages of the site manifest with the accounts holding them.
+    /// `scan` also lists every other account owned by the program, `modified`
+    /// looks up the last transaction of each entry, one request per entry.
+    pub fn list(&self, scan: bool, modified: bool) -> W3Result<Listing> {
+        let mut paths: Vec<String> = match self.get_manifest()? {
+            Some(manifest) => manifest.files.into_keys().collect(),
+            None if scan => Vec::new(),
+            None => {
+                return Err(W3ClientError::NotFound(
+                    "site manifest, scan the program accounts with --all".into(),
+                ))
+            }
+        };
+        if !paths.iter().any(|path| path == MANIFEST_PATH) {
+            paths.push(MANIFEST_PATH.to_string());
+        }
+
+        let mut listing = Listing::default();
+        let mut listed = BTreeSet::new();
+        for chunk in paths.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let keys: Vec<Pubkey> = chunk
+                .iter()
+                .map(|path| self.content.find_program_address_by_text(path).0)
+                .collect();
+            let accounts = self.connection.get_multiple_accounts(&keys)?;
+            for ((path, key), account) in chunk.iter().zip(keys).zip(accounts) {
+                match account {
+                    Some(account) => {
+                        let accounts = self.page_accounts(path, key, account)?;
+                        let mut entry = self.page_entry(path, &accounts)?;
+                        if modified {
+                            entry.last_modified = self.last_modified(&key)?;
+                        }
+                        listing.entries.push(entry);
+                        listed.extend(
+                            accounts
+                                .into_iter()
+                                .map(|(key, account)| (key, account.lamports)),
+                        );
+                    }
+                    // no manifest before the first sync
+                    None if path == MANIFEST_PATH => {}
+                    None => warn!("Page {} of the site manifest is missing", path),
+                }
+            }
+        }
+
+        if scan {
+            let known: BTreeSet<Pubkey> = listed.iter().map(|(key, _)| *key).collect();
+            for (key, account) in self
+                .connection
+                .get_program_accounts(&self.content.program)?
+            {
+                if known.contains(&key) {
+                    continue;
+                }
+                listed.insert((key, account.lamports));
+                let mut entry = self.account_entry(key, account);
+                if modified {
+                    entry.last_modified = self.last_modified(&key)?;
+                }
+                listing.entries.push(entry);
+            }
+        }
+
+        listing.rent = listed.iter().map(|(_, lamports)| lamports).sum();
+        Ok(listing)
+    }
+
+    /// The page account of `path` followed by its trunks or blob.
+    fn page_accounts(
+        &self,
+        path: &str,
+        key: Pubkey,
+        account: Account,
+    ) -> W3Result<Vec<(Pubkey, Account)>> {
+        let parts: Vec<Pubkey> = match PageData::from_account_data(&account.data) {
+            Ok(PageData::TrunkPage { trunks }) => (0..=trunks)
+                .map(|trunk_no| {
+                    self.content
+                        .find_program_address_by_text_suffix(path, &[trunk_no])
+                        .0
+                })
+                .collect(),
+            Ok(PageData::BlobPage { hash }) => vec![self.content.find_blob_address(&hash).0],
+            _ => Vec::new(),
+        };
+        let mut accounts = vec![(key, account)];
+        for keys in parts.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let found = self.connection.get_multiple_accounts(keys)?;
+            for (key, account) in keys.iter().zip(found) {
+                match account {
+                    Some(account) => accounts.push((*key, account)),
+                    None => warn!("Account {} of page {} is missing", key, path),
+                }
+            }
+        }
+        Ok(accounts)
+    }
+
+    fn page_entry(&self, path: &str, accounts: &[(Pubkey, Account)]) -> W3Result<ListEntry> {
+        let page = PageData::from_account_data(&accounts[0].1.data)
+            .map_err(|e| W3ClientError::Decode(format!("page {}: {}", path, e)))?;
+        let parts = &accounts[1..];
+        let (kind, size) = match page {
+            PageData::RawData { data } => (EntryKind::Raw, data.len() as u64),
+            PageData::TrunkPage { .. } => (
+                EntryKind::Trunks,
+                parts
+                    .iter()
+                    .map(|(_, account)| account.data.len() as u64)
+                    .sum(),
+            ),
+            PageData::BlobPage { .. } => (
+                EntryKind::Blob,
+                parts
+                    .first()
+                    .and_then(|(_, account)| BlobHeader::from_account_data(&account.data))
+                    .map_or(0, |header| header.size as u64),
+            ),
+            PageData::AccountPage { account, .. } => (
+                EntryKind::Account,
+                self.connection
+                    .get_multiple_accounts(&[account])?
+                    .remove(0)
+                    .map_or(0, |account| account.data.len() as u64),
+            ),
+        };
+        Ok(ListEntry::new(path.to_string(), kind, size, accounts))
+    }
+
+    /// Entry of a program account no listed page refers to, names and blobs
+    /// are recognized when their address matches their content.
+    pub fn account_entry(&self, key: Pubkey, account: Account) -> ListEntry {
+        let size = account.data.len() as u64;
+        if let Ok(config) = NameConfig::try_from_slice(&account.data) {
+            if self.content.find_name_address(&config.name).0 == key {
+                return ListEntry::new(config.name, EntryKind::Name, size, &[(key, account)]);
+            }
+        }
+        if let Some(header) = BlobHeader::from_account_data(&account.data) {
+            if self.content.find_blob_address(&header.hash).0 == key {
+                let path = format!("<blob {}>", Hash::new_from_array(header.hash));
+                let size = header.size as u64;
+                return ListEntry::new(path, EntryKind::Blob, size, &[(key, account)]);
+            }
+        }
+        let path = format!("<{}>", key);
+        ListEntry::new(path, EntryKind::Unknown, size, &[(key, account)])
+    }
+
+    /// Block time of the latest transaction touching `key`.
+    fn last_modified(&self, key: &Pubkey) -> W3Result<Option<i64>> {
+        let signatures = self.connection.get_signatures_for_address_with_config(
+            key,
+            GetConfirmedSignaturesForAddress2Config {
+                limit: Some(1),
+                ..GetConfirmedSignaturesForAddress2Config::default()
+            },
+        )?;
+        Ok(signatures
+            .first()
+            .and_then(|signature| signature.block_time))
+    }
+}
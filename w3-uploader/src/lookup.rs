@@ -0,0 +1,168 @@
+//! Address lookup table of a site, letting batch writes carry more files.
+//!
+//! A `PutContentBatch` lists the page account of every file it writes, 32
+//! bytes each. Once those accounts are in a lookup table a v0 transaction
+//! refers to each by a one byte index, so more small files fit a batch.
+//! The table is created once with `lookup-table create` and set as
+//! `lookup_table` in the config. Uploads extend it with the page accounts of
+//! new small files when the signer is its authority; transactions fitting a
+//! legacy message are still sent as one.
+
+use {
+    log::{info, warn},
+    solana_sdk::{
+        address_lookup_table::{
+            instruction::{create_lookup_table_signed, extend_lookup_table},
+            state::{AddressLookupTable, LookupTableMeta, LOOKUP_TABLE_MAX_ADDRESSES},
+            AddressLookupTableAccount,
+        },
+        clock::Slot,
+        commitment_config::CommitmentConfig,
+        pubkey::Pubkey,
+        system_program,
+    },
+    std::{fs, path::Path, thread, time::Duration},
+};
+
+use crate::{
+    client::W3Client,
+    error::{W3ClientError, W3Result},
+    solana_trait::SolanaTransaction,
+};
+
+/// Addresses added by one extend transaction.
+const EXTEND_CHUNK: usize = 20;
+const SLOT_POLL_INTERVAL: Duration = Duration::from_millis(400);
+
+impl W3Client {
+    /// Creates an empty lookup table owned by the signer, returns its address.
+    pub fn create_lookup_table(&self) -> W3Result<Pubkey> {
+        // the slot seeds the table address and must still be a recent one
+        let slot = self
+            .connection
+            .get_slot_with_commitment(CommitmentConfig::finalized())?;
+        let (instruction, table) =
+            create_lookup_table_signed(self.signer.pubkey(), self.fee_payer().pubkey(), slot);
+        self.send_instruction(&self.fee_payer().pubkey(), &self.signers(), instruction)?;
+        info!("Created lookup table {}", table);
+        Ok(table)
+    }
+
+    pub fn load_lookup_table(&self, table: &Pubkey) -> W3Result<AddressLookupTableAccount> {
+        let (_, addresses) = self.read_lookup_table(table)?;
+        Ok(AddressLookupTableAccount {
+            key: *table,
+            addresses,
+        })
+    }
+
+    fn read_lookup_table(&self, table: &Pubkey) -> W3Result<(LookupTableMeta, Vec<Pubkey>)> {
+        let account = self
+            .get_account_info(table)?
+            .ok_or_else(|| W3ClientError::NotFound(format!("lookup table {}", table)))?;
+        let state = AddressLookupTable::deserialize(&account.data)
+            .map_err(|e| W3ClientError::Decode(format!("lookup table {}: {}", table, e)))?;
+        Ok((state.meta, state.addresses.to_vec()))
+    }
+
+    /// Adds the `addresses` missing from `table`, as many as it has room for,
+    /// and waits until they can be looked up.
+    pub fn extend_lookup_table(
+        &self,
+        table: &Pubkey,
+        addresses: &[Pubkey],
+    ) -> W3Result<AddressLookupTableAccount> {
+        let (meta, existing) = self.read_lookup_table(table)?;
+        if meta.authority != Some(self.signer.pubkey()) {
+            return Err(W3ClientError::InvalidArgument(format!(
+                "lookup table {} is not owned by {}",
+                table,
+                self.signer.pubkey()
+            )));
+        }
+        let mut missing: Vec<Pubkey> = Vec::new();
+        for address in addresses {
+            if !existing.contains(address) && !missing.contains(address) {
+                missing.push(*address);
+            }
+        }
+        let room = LOOKUP_TABLE_MAX_ADDRESSES - existing.len();
+        if missing.len() > room {
+            warn!(
+                "Lookup table {} is full, {} accounts are left out",
+                table,
+                missing.len() - room
+            );
+            missing.truncate(room);
+        }
+        if missing.is_empty() {
+            return self.load_lookup_table(table);
+        }
+
+        for chunk in missing.chunks(EXTEND_CHUNK) {
+            let instruction = extend_lookup_table(
+                *table,
+                self.signer.pubkey(),
+                Some(self.fee_payer().pubkey()),
+                chunk.to_vec(),
+            );
+            self.send_instruction(&self.fee_payer().pubkey(), &self.signers(), instruction)?;
+        }
+        info!("Added {} accounts to lookup table {}", missing.len(), table);
+        let (meta, addresses) = self.read_lookup_table(table)?;
+        self.wait_past_slot(meta.last_extended_slot)?;
+        Ok(AddressLookupTableAccount {
+            key: *table,
+            addresses,
+        })
+    }
+
+    /// Addresses only become usable in the slot after they were added.
+    fn wait_past_slot(&self, slot: Slot) -> W3Result<()> {
+        while self.connection.get_slot()? <= slot {
+            thread::sleep(SLOT_POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    /// Accounts written by the batches of `dir`: the page accounts of the
+    /// files small enough to be batched, and the system program.
+    pub fn hot_accounts(&self, dir: &Path) -> W3Result<Vec<Pubkey>> {
+        let mut files = Vec::new();
+        self.collect_files(dir, dir, &mut files)?;
+        let mut accounts = vec![system_program::ID];
+        for (web_path, full_path) in files {
+            if fs::metadata(&full_path)?.len() as usize <= self.simple_size_for(&web_path) {
+                accounts.push(self.helper.find_program_address_by_text(&web_path).0);
+            }
+        }
+        Ok(accounts)
+    }
+
+    /// Loads the configured lookup table for uploading `dir`, adding the hot
+    /// accounts of `dir` when the signer is its authority.
+    pub fn use_lookup_table(&mut self, dir: &Path) -> W3Result<()> {
+        let table = match self.lookup_table {
+            Some(table) => table,
+            None => return Ok(()),
+        };
+        let (meta, _) = self.read_lookup_table(&table)?;
+        if meta.deactivation_slot != Slot::MAX {
+            return Err(W3ClientError::Config(format!(
+                "lookup table {} is deactivated",
+                table
+            )));
+        }
+        let loaded = if meta.authority == Some(self.signer.pubkey()) {
+            self.extend_lookup_table(&table, &self.hot_accounts(dir)?)?
+        } else {
+            info!(
+                "Lookup table {} is not owned by the signer, using it as is",
+                table
+            );
+            self.load_lookup_table(&table)?
+        };
+        self.lookup_tables = vec![loaded];
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use solana_sdk::{
+    address_lookup_table::AddressLookupTableAccount,
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, Message, VersionedMessage},
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+};
+
+/// Size in bytes of the signed legacy transaction carrying `instructions`.
+pub fn transaction_size(instructions: &[Instruction], payer: &Pubkey) -> usize {
+    let message = Message::new(instructions, Some(payer));
+    let signatures = message.header.num_required_signatures as usize;
+    // compact-u16 signature count, one byte below 128 signatures
+    1 + signatures * 64 + message.serialize().len()
+}
+
+/// Whether `instructions` fit a packet with `reserved` bytes left for the
+/// instructions added when sending, e.g. compute budget ones.
+pub fn fits_in_packet(instructions: &[Instruction], payer: &Pubkey, reserved: usize) -> bool {
+    transaction_size(instructions, payer) + reserved <= PACKET_DATA_SIZE
+}
+
+/// Accounts a transaction may lock, lookup table entries included.
+pub const MAX_ACCOUNT_LOCKS: usize = 64;
+
+/// Size in bytes of the signed v0 transaction carrying `instructions`, the
+/// accounts found in `tables` are looked up instead of listed. `None` when
+/// the instructions cannot be compiled to a v0 message.
+pub fn versioned_transaction_size(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    tables: &[AddressLookupTableAccount],
+) -> Option<usize> {
+    let message = v0::Message::try_compile(payer, instructions, tables, Hash::default()).ok()?;
+    let signatures = message.header.num_required_signatures as usize;
+    Some(1 + signatures * 64 + VersionedMessage::V0(message).serialize().len())
+}
+
+/// Whether `instructions` fit a packet as a v0 transaction using `tables`,
+/// `reserved` bytes aside, without locking too many accounts.
+pub fn fits_in_versioned_packet(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    tables: &[AddressLookupTableAccount],
+    reserved: usize,
+) -> bool {
+    let mut accounts: Vec<&Pubkey> = instructions
+        .iter()
+        .flat_map(|instruction| {
+            std::iter::once(&instruction.program_id)
+                .chain(instruction.accounts.iter().map(|meta| &meta.pubkey))
+        })
+        .chain(std::iter::once(payer))
+        .collect();
+    accounts.sort();
+    accounts.dedup();
+    accounts.len() <= MAX_ACCOUNT_LOCKS
+        && versioned_transaction_size(instructions, payer, tables)
+            .is_some_and(|size| size + reserved <= PACKET_DATA_SIZE)
+}
+
+/// Largest body an instruction built by `build` can carry while still fitting
+/// a transaction on its own, `reserved` bytes aside.
+pub fn max_body_size(
+    payer: &Pubkey,
+    reserved: usize,
+    build: impl Fn(Vec<u8>) -> Instruction,
+) -> usize {
+    max_shared_body_size(payer, reserved, 1, |_, body| build(body))
+}
+
+/// Largest body each of `count` instructions built by `build` from their
+/// index can carry while all of them share a transaction.
+pub fn max_shared_body_size(
+    payer: &Pubkey,
+    reserved: usize,
+    count: usize,
+    build: impl Fn(usize, Vec<u8>) -> Instruction,
+) -> usize {
+    // probe with a body long enough for its length to take the same two
+    // compact-u16 bytes as any body close to the limit
+    const PROBE: usize = 128;
+    let instructions: Vec<Instruction> = (0..count).map(|idx| build(idx, vec![0; PROBE])).collect();
+    let size = transaction_size(&instructions, payer) + reserved;
+    (PACKET_DATA_SIZE + PROBE * count).saturating_sub(size) / count
+}
+
+/// Fills transactions with as many instructions as fit in the packet limit,
+/// keeping their order.
+pub fn pack_instructions(
+    instructions: Vec<Instruction>,
+    payer: &Pubkey,
+    reserved: usize,
+) -> Vec<Vec<Instruction>> {
+    let mut transactions = Vec::new();
+    let mut current: Vec<Instruction> = Vec::new();
+    for instruction in instructions {
+        current.push(instruction);
+        if current.len() > 1 && !fits_in_packet(&current, payer, reserved) {
+            let instruction = current.pop().unwrap();
+            transactions.push(std::mem::replace(&mut current, vec![instruction]));
+        }
+    }
+    if !current.is_empty() {
+        transactions.push(current);
+    }
+    transactions
+}
@@ -0,0 +1,222 @@
+use {
+    borsh::BorshDeserialize,
+    log::warn,
+    serde::Serialize,
+    solana_sdk::{
+        instruction::Instruction, message::Message, native_token::lamports_to_sol, pubkey::Pubkey,
+    },
+    std::{
+        collections::{BTreeMap, HashMap},
+        path::Path,
+    },
+};
+
+use crate::{
+    client::W3Client,
+    engine::UploadJob,
+    error::W3Result,
+    reader::MAX_MULTIPLE_ACCOUNTS,
+    sdk::{BlobHeader, InstructionData, PageData},
+};
+
+/// An account written by the upload and the rent it still needs.
+#[derive(Serialize, Debug, Clone)]
+pub struct AccountCost {
+    pub address: String,
+    pub size: usize,
+    /// Size on chain today, `None` when the account does not exist yet.
+    pub current_size: Option<usize>,
+    pub rent: u64,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct JobCost {
+    pub paths: Vec<String>,
+    pub accounts: Vec<AccountCost>,
+    pub transactions: usize,
+    pub fee: u64,
+    pub rent: u64,
+    pub total: u64,
+}
+
+/// Lamports an upload would spend, computed without signing anything.
+#[derive(Serialize, Debug, Default)]
+pub struct UploadPlan {
+    pub lamports_per_signature: u64,
+    pub jobs: Vec<JobCost>,
+    pub transactions: usize,
+    pub fee: u64,
+    pub rent: u64,
+    pub total: u64,
+}
+
+impl UploadPlan {
+    pub fn print(&self) {
+        for job in &self.jobs {
+            println!(
+                "{:<40} {:>3} tx {:>12.9} SOL fee {:>12.9} SOL rent",
+                job.paths.join(", "),
+                job.transactions,
+                lamports_to_sol(job.fee),
+                lamports_to_sol(job.rent)
+            );
+            for account in &job.accounts {
+                let current = match account.current_size {
+                    Some(size) => format!("{} bytes", size),
+                    None => "new".to_string(),
+                };
+                println!(
+                    "    {} {} bytes ({}) {:.9} SOL",
+                    account.address,
+                    account.size,
+                    current,
+                    lamports_to_sol(account.rent)
+                );
+            }
+        }
+        println!(
+            "{} transactions, {:.9} SOL fees + {:.9} SOL rent = {:.9} SOL",
+            self.transactions,
+            lamports_to_sol(self.fee),
+            lamports_to_sol(self.rent),
+            lamports_to_sol(self.total)
+        );
+    }
+}
+
+/// Accounts an instruction writes with the size the program gives them.
+pub fn written_accounts(instruction: &Instruction) -> Vec<(Pubkey, usize)> {
+    let raw_size = |body: &[u8]| {
+        PageData::RawData {
+            data: body.to_vec(),
+        }
+        .account_len()
+    };
+    let key = |idx: usize| instruction.accounts[idx].pubkey;
+    match InstructionData::try_from_slice(&instruction.data) {
+        Ok(InstructionData::PutContent { body, .. }) => vec![(key(1), raw_size(&body))],
+        Ok(InstructionData::PutContentBatch { entries }) => entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| (key(idx + 2), raw_size(&entry.body)))
+            .collect(),
+        Ok(InstructionData::PutTrunkContent { trunk_no, body, .. }) => vec![
+            (
+                key(1),
+                PageData::TrunkPage { trunks: trunk_no }.account_len(),
+            ),
+            (key(2), body.len()),
+        ],
+        Ok(InstructionData::PutBlob { size, .. }) => {
+            vec![(key(1), BlobHeader::LEN + size as usize)]
+        }
+        Ok(InstructionData::LinkBlob { hash, .. }) => {
+            vec![(key(1), PageData::BlobPage { hash }.account_len())]
+        }
+        _ => Vec::new(),
+    }
+}
+
+impl W3Client {
+    /// Costs of uploading the files of `dir` as `visit_dirs` would.
+    pub fn plan_dirs(&self, dir: &Path) -> W3Result<UploadPlan> {
+        let mut files = Vec::new();
+        self.collect_files(dir, dir, &mut files)?;
+        self.check_secrets(&files)?;
+        let jobs = self.upload_jobs(&files)?;
+        self.plan_jobs(&jobs)
+    }
+
+    pub fn plan_jobs(&self, jobs: &[UploadJob]) -> W3Result<UploadPlan> {
+        let payer = self.fee_payer().pubkey();
+
+        // an account written by several transactions is charged once, to the
+        // first job writing it, at the largest size it is given
+        let mut sizes: BTreeMap<Pubkey, usize> = BTreeMap::new();
+        let mut owners: HashMap<Pubkey, usize> = HashMap::new();
+        for (idx, job) in jobs.iter().enumerate() {
+            for instruction in job.stages.iter().flatten().flatten() {
+                for (account, size) in written_accounts(instruction) {
+                    let entry = sizes.entry(account).or_default();
+                    *entry = (*entry).max(size);
+                    owners.entry(account).or_insert(idx);
+                }
+            }
+        }
+
+        let addresses: Vec<Pubkey> = sizes.keys().cloned().collect();
+        let mut current = HashMap::new();
+        for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let accounts = self.connection.get_multiple_accounts(chunk)?;
+            for (address, account) in chunk.iter().zip(accounts) {
+                if let Some(account) = account {
+                    current.insert(*address, account);
+                }
+            }
+        }
+
+        let mut rents: HashMap<usize, u64> = HashMap::new();
+        let mut plan = UploadPlan {
+            lamports_per_signature: self.lamports_per_signature()?,
+            jobs: jobs
+                .iter()
+                .map(|job| JobCost {
+                    paths: job.paths.clone(),
+                    ..JobCost::default()
+                })
+                .collect(),
+            ..UploadPlan::default()
+        };
+        for (address, size) in sizes {
+            let rent = match rents.get(&size) {
+                Some(rent) => *rent,
+                None => {
+                    let rent = self
+                        .connection
+                        .get_minimum_balance_for_rent_exemption(size)?;
+                    rents.insert(size, rent);
+                    rent
+                }
+            };
+            let account = current.get(&address);
+            let lamports = account.map(|account| account.lamports).unwrap_or_default();
+            plan.jobs[owners[&address]].accounts.push(AccountCost {
+                address: address.to_string(),
+                size,
+                current_size: account.map(|account| account.data.len()),
+                rent: rent.saturating_sub(lamports),
+            });
+        }
+
+        for (job, cost) in jobs.iter().zip(plan.jobs.iter_mut()) {
+            for instructions in job.stages.iter().flatten() {
+                let message = Message::new(instructions, Some(&payer));
+                cost.fee +=
+                    message.header.num_required_signatures as u64 * plan.lamports_per_signature;
+                cost.transactions += 1;
+            }
+            cost.rent = cost.accounts.iter().map(|account| account.rent).sum();
+            cost.total = cost.fee + cost.rent;
+            plan.transactions += cost.transactions;
+            plan.fee += cost.fee;
+            plan.rent += cost.rent;
+        }
+        plan.total = plan.fee + plan.rent;
+        Ok(plan)
+    }
+
+    fn lamports_per_signature(&self) -> W3Result<u64> {
+        let mut message = Message::new(&[], Some(&self.fee_payer().pubkey()));
+        message.recent_blockhash = self.blockhash.get(&self.connection)?;
+        match self.connection.get_fee_for_message(&message) {
+            Ok(fee) => Ok(fee),
+            Err(e) => {
+                warn!(
+                    "Cannot get the signature fee, assuming 5000 lamports: {:?}",
+                    e
+                );
+                Ok(5000)
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use {
+    log::{info, warn},
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        fs,
+        path::{Component, Path, PathBuf},
+    },
+};
+
+use crate::{
+    client::W3Client,
+    error::{W3ClientError, W3Result},
+    schema,
+    sdk::{BlobHeader, PageData},
+    solana_trait::SolanaTransaction,
+    sync::manifest_entry,
+};
+
+/// `getMultipleAccounts` accepts at most 100 keys per call.
+pub(crate) const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// What a reader finds at a web path.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PageContent {
+    Missing,
+    /// The page exists but some of its parts do not, e.g. an interrupted
+    /// upload.
+    Incomplete(String),
+    Complete(Vec<u8>),
+}
+
+#[derive(Debug, Default)]
+pub struct PullSummary {
+    pub written: Vec<String>,
+    pub missing: Vec<String>,
+    /// Downloaded content differing from the site manifest.
+    pub mismatched: Vec<String>,
+}
+
+impl PullSummary {
+    pub fn print(&self) {
+        for (label, paths) in [("missing", &self.missing), ("mismatched", &self.mismatched)] {
+            for path in paths {
+                warn!("{:>10}: {}", label, path);
+            }
+        }
+        info!(
+            "{} written, {} missing, {} mismatched",
+            self.written.len(),
+            self.missing.len(),
+            self.mismatched.len()
+        );
+    }
+}
+
+/// File of `web_path` under `dir`, `None` when the path would leave `dir`.
+pub fn local_path(dir: &Path, web_path: &str) -> Option<PathBuf> {
+    let relative = Path::new(web_path.trim_start_matches('/'));
+    let safe = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    match safe && relative.components().next().is_some() {
+        true => Some(dir.join(relative)),
+        false => None,
+    }
+}
+
+impl W3Client {
+    /// Page stored at `web_path`, `None` when there is none.
+    pub fn get_page_data(&self, web_path: &str) -> W3Result<Option<PageData>> {
+        let (page_account, _) = self.content.find_program_address_by_text(web_path);
+        match self.get_account_info(&page_account)? {
+            Some(account) => PageData::from_account_data(&account.data)
+                .map(Some)
+                .map_err(|e| W3ClientError::Decode(format!("page {}: {}", web_path, e))),
+            None => Ok(None),
+        }
+    }
+
+    /// Content served at `web_path`, reassembled from its trunks, blob or
+    /// source account. `None` when the page or one of its parts is missing.
+    pub fn get_page(&self, web_path: &str) -> W3Result<Option<Vec<u8>>> {
+        Ok(match self.fetch_page(web_path)? {
+            PageContent::Complete(data) => Some(data),
+            PageContent::Incomplete(reason) => {
+                warn!("Page {} is incomplete: {}", web_path, reason);
+                None
+            }
+            PageContent::Missing => None,
+        })
+    }
+
+    pub fn fetch_page(&self, web_path: &str) -> W3Result<PageContent> {
+        match self.get_page_data(web_path)? {
+            Some(page) => self.page_content(web_path, page),
+            None => Ok(PageContent::Missing),
+        }
+    }
+
+    /// Like `fetch_page` for many paths, their page accounts are read in
+    /// batches.
+    pub fn fetch_pages(&self, web_paths: &[String]) -> W3Result<Vec<PageContent>> {
+        let mut pages = Vec::with_capacity(web_paths.len());
+        for chunk in web_paths.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let keys: Vec<Pubkey> = chunk
+                .iter()
+                .map(|web_path| self.content.find_program_address_by_text(web_path).0)
+                .collect();
+            let accounts = self.connection.get_multiple_accounts(&keys)?;
+            for (web_path, account) in chunk.iter().zip(accounts) {
+                pages.push(match account {
+                    Some(account) => {
+                        let page = PageData::from_account_data(&account.data).map_err(|e| {
+                            W3ClientError::Decode(format!("page {}: {}", web_path, e))
+                        })?;
+                        self.page_content(web_path, page)?
+                    }
+                    None => PageContent::Missing,
+                });
+            }
+        }
+        Ok(pages)
+    }
+
+    /// Resolves the content behind `page`, reading its trunks, blob or
+    /// source account.
+    pub fn page_content(&self, web_path: &str, page: PageData) -> W3Result<PageContent> {
+        Ok(match page {
+            PageData::RawData { data } => PageContent::Complete(data),
+            PageData::TrunkPage { trunks } => {
+                let trunk_accounts: Vec<Pubkey> = (0..=trunks)
+                    .map(|trunk_no| {
+                        self.content
+                            .find_program_address_by_text_suffix(web_path, &[trunk_no])
+                            .0
+                    })
+                    .collect();
+                let mut content = Vec::new();
+                for keys in trunk_accounts.chunks(MAX_MULTIPLE_ACCOUNTS) {
+                    let accounts = self.connection.get_multiple_accounts(keys)?;
+                    for (key, account) in keys.iter().zip(accounts) {
+                        match account {
+                            Some(account) => content.extend(account.data),
+                            None => {
+                                return Ok(PageContent::Incomplete(format!(
+                                    "trunk account {} is missing",
+                                    key
+                                )))
+                            }
+                        }
+                    }
+                }
+                PageContent::Complete(content)
+            }
+            PageData::BlobPage { hash } => {
+                let (blob_account, _) = self.content.find_blob_address(&hash);
+                let account = match self.get_account_info(&blob_account)? {
+                    Some(account) => account,
+                    None => {
+                        return Ok(PageContent::Incomplete(format!(
+                            "blob account {} is missing",
+                            blob_account
+                        )))
+                    }
+                };
+                let header = BlobHeader::from_account_data(&account.data);
+                let body = header.as_ref().and_then(|header| {
+                    account
+                        .data
+                        .get(BlobHeader::LEN..BlobHeader::LEN + header.size as usize)
+                });
+                match (header, body) {
+                    (Some(header), Some(body)) if header.sealed => {
+                        PageContent::Complete(body.to_vec())
+                    }
+                    _ => PageContent::Incomplete("blob is not completely uploaded".into()),
+                }
+            }
+            PageData::AccountPage { account, decoder } => match self.get_account_info(&account)? {
+                Some(source) => {
+                    PageContent::Complete(schema::decode_account(&decoder, &source.data)?)
+                }
+                None => PageContent::Incomplete(format!("source account {} is missing", account)),
+            },
+        })
+    }
+
+    /// Downloads `paths` into `dir`, or every file of the site manifest when
+    /// `paths` is empty.
+    pub fn pull(&self, dir: &Path, paths: &[String]) -> W3Result<PullSummary> {
+        let manifest = self.get_manifest()?;
+        let paths = match (paths.is_empty(), &manifest) {
+            (false, _) => paths.to_vec(),
+            (true, Some(manifest)) => manifest.files.keys().cloned().collect(),
+            (true, None) => {
+                return Err(W3ClientError::NotFound(
+                    "site manifest, give the paths to download".into(),
+                ))
+            }
+        };
+
+        let mut summary = PullSummary::default();
+        for web_path in paths {
+            let file = local_path(dir, &web_path).ok_or_else(|| {
+                W3ClientError::InvalidArgument(format!("unsafe path {}", web_path))
+            })?;
+            let data = match self.get_page(&web_path)? {
+                Some(data) => data,
+                None => {
+                    summary.missing.push(web_path);
+                    continue;
+                }
+            };
+            let expected = manifest
+                .as_ref()
+                .and_then(|manifest| manifest.files.get(&web_path));
+            if expected.is_some_and(|entry| *entry != manifest_entry(&data)) {
+                summary.mismatched.push(web_path.clone());
+            }
+            if let Some(parent) = file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&file, data)?;
+            info!("Downloaded {} to {}", web_path, file.display());
+            summary.written.push(web_path);
+        }
+        Ok(summary)
+    }
+}
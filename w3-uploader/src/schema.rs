@@ -0,0 +1,196 @@
+//! Decoding of account-backed pages.
+//!
+//! A Borsh schema is a `,` separated list of `name:type` fields. Types are
+//! `bool`, `u8`..`u128`, `i8`..`i128`, `string`, `pubkey`, `bytes`,
+//! `[u8;N]`, `vec<T>` and `option<T>`. Fields named with a leading `_`, such
+//! as an Anchor discriminator `_discriminator:[u8;8]`, are read but not shown.
+
+use {
+    crate::{
+        error::{W3ClientError, W3Result},
+        sdk::AccountDecoder,
+    },
+    serde_json::{Map, Value},
+    solana_sdk::pubkey::Pubkey,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    String,
+    Pubkey,
+    Bytes,
+    Fixed(usize),
+    Vec(Box<FieldType>),
+    Option(Box<FieldType>),
+}
+
+impl FieldType {
+    fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if let Some(inner) = text.strip_prefix("vec<").and_then(|t| t.strip_suffix('>')) {
+            return Ok(Self::Vec(Box::new(Self::parse(inner)?)));
+        }
+        if let Some(inner) = text
+            .strip_prefix("option<")
+            .and_then(|t| t.strip_suffix('>'))
+        {
+            return Ok(Self::Option(Box::new(Self::parse(inner)?)));
+        }
+        if let Some(len) = text.strip_prefix("[u8;").and_then(|t| t.strip_suffix(']')) {
+            return len
+                .trim()
+                .parse()
+                .map(Self::Fixed)
+                .map_err(|e| format!("bad array length {}: {}", len, e));
+        }
+        match text {
+            "bool" => Ok(Self::Bool),
+            "u8" => Ok(Self::U8),
+            "u16" => Ok(Self::U16),
+            "u32" => Ok(Self::U32),
+            "u64" => Ok(Self::U64),
+            "u128" => Ok(Self::U128),
+            "i8" => Ok(Self::I8),
+            "i16" => Ok(Self::I16),
+            "i32" => Ok(Self::I32),
+            "i64" => Ok(Self::I64),
+            "i128" => Ok(Self::I128),
+            "string" => Ok(Self::String),
+            "pubkey" => Ok(Self::Pubkey),
+            "bytes" => Ok(Self::Bytes),
+            _ => Err(format!("unknown type: {}", text)),
+        }
+    }
+}
+
+pub fn parse_schema(schema: &str) -> W3Result<Vec<(String, FieldType)>> {
+    schema
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(|field| match field.split_once(':') {
+            Some((name, field_type)) => {
+                Ok((name.trim().to_string(), FieldType::parse(field_type)?))
+            }
+            None => Err(format!("field without type: {}", field)),
+        })
+        .collect::<Result<_, _>>()
+        .map_err(W3ClientError::Decode)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < len {
+            return Err("account data too short for schema".to_string());
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn take_len(&mut self) -> Result<usize, String> {
+        Ok(u32::from_le_bytes(self.take_array()?) as usize)
+    }
+
+    fn read(&mut self, field_type: &FieldType) -> Result<Value, String> {
+        Ok(match field_type {
+            FieldType::Bool => Value::from(self.take(1)?[0] != 0),
+            FieldType::U8 => Value::from(self.take(1)?[0]),
+            FieldType::U16 => Value::from(u16::from_le_bytes(self.take_array()?)),
+            FieldType::U32 => Value::from(u32::from_le_bytes(self.take_array()?)),
+            FieldType::U64 => Value::from(u64::from_le_bytes(self.take_array()?)),
+            // 128 bit integers do not fit JSON numbers, render them as strings
+            FieldType::U128 => Value::from(u128::from_le_bytes(self.take_array()?).to_string()),
+            FieldType::I8 => Value::from(self.take(1)?[0] as i8),
+            FieldType::I16 => Value::from(i16::from_le_bytes(self.take_array()?)),
+            FieldType::I32 => Value::from(i32::from_le_bytes(self.take_array()?)),
+            FieldType::I64 => Value::from(i64::from_le_bytes(self.take_array()?)),
+            FieldType::I128 => Value::from(i128::from_le_bytes(self.take_array()?).to_string()),
+            FieldType::String => {
+                let len = self.take_len()?;
+                Value::from(String::from_utf8_lossy(self.take(len)?).to_string())
+            }
+            FieldType::Pubkey => {
+                Value::from(Pubkey::new_from_array(self.take_array()?).to_string())
+            }
+            FieldType::Bytes => {
+                let len = self.take_len()?;
+                Value::from(to_hex(self.take(len)?))
+            }
+            FieldType::Fixed(len) => Value::from(to_hex(self.take(*len)?)),
+            FieldType::Vec(inner) => {
+                let len = self.take_len()?;
+                let mut items = Vec::new();
+                for _ in 0..len {
+                    items.push(self.read(inner)?);
+                }
+                Value::Array(items)
+            }
+            FieldType::Option(inner) => match self.take(1)?[0] {
+                0 => Value::Null,
+                _ => self.read(inner)?,
+            },
+        })
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn borsh_to_json(schema: &str, data: &[u8]) -> W3Result<Value> {
+    let fields = parse_schema(schema)?;
+    let mut reader = Reader { data };
+    let mut object = Map::new();
+    for (name, field_type) in fields {
+        let value = reader.read(&field_type).map_err(W3ClientError::Decode)?;
+        if !name.starts_with('_') {
+            object.insert(name, value);
+        }
+    }
+    Ok(Value::Object(object))
+}
+
+/// Renders the data of the account behind an `AccountPage` as page body.
+pub fn decode_account(decoder: &AccountDecoder, data: &[u8]) -> W3Result<Vec<u8>> {
+    match decoder {
+        AccountDecoder::Raw => Ok(data.to_vec()),
+        AccountDecoder::ByteRange { offset, length } => {
+            let start = *offset as usize;
+            let end = start + *length as usize;
+            data.get(start..end)
+                .map(|range| range.to_vec())
+                .ok_or_else(|| {
+                    W3ClientError::Decode(format!(
+                        "range {}..{} out of {} bytes",
+                        start,
+                        end,
+                        data.len()
+                    ))
+                })
+        }
+        AccountDecoder::BorshJson { schema } => {
+            let value = borsh_to_json(schema, data)?;
+            Ok(serde_json::to_vec(&value)?)
+        }
+    }
+}
@@ -1,11 +1,10 @@
 use {
-    crate::client::W3Client,
+    crate::error::{W3ClientError, W3Result},
     borsh::{BorshDeserialize, BorshSerialize},
-    serde::{Deserialize, Serialize},
     solana_client::rpc_client::RpcClient,
-    solana_program::pubkey::{self, Pubkey},
-    solana_sdk::{commitment_config::CommitmentConfig, signer::keypair::Keypair},
-    std::{fs, str::FromStr},
+    solana_program::pubkey,
+    solana_sdk::{bs58, commitment_config::CommitmentConfig, signer::keypair::Keypair},
+    std::fmt,
 };
 
 #[derive(Clone)]
@@ -17,17 +16,19 @@ pub enum Network {
     Custom(String),
 }
 
-impl Network {
-    pub fn to_string(&self) -> String {
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Local => "localnet".to_string(),
-            Self::Dev => "devnet".to_string(),
-            Self::Test => "testnet".to_string(),
-            Self::MainBeta => "mainnet".to_string(),
-            Self::Custom(url) => url.to_string(),
+            Self::Local => write!(f, "localnet"),
+            Self::Dev => write!(f, "devnet"),
+            Self::Test => write!(f, "testnet"),
+            Self::MainBeta => write!(f, "mainnet"),
+            Self::Custom(url) => write!(f, "{}", url),
         }
     }
+}
 
+impl Network {
     pub fn from_string(network_name: &str) -> Self {
         match network_name {
             "localnet" => Self::Local,
@@ -89,6 +90,52 @@ pub enum InstructionData {
         program: pubkey::Pubkey,
         default_page: String,
     },
+    PutBlob {
+        hash: [u8; 32],
+        size: u32,
+        offset: u32,
+        body: Vec<u8>,
+    },
+    LinkBlob {
+        path: String,
+        hash: [u8; 32],
+    },
+    UnlinkBlob {
+        path: String,
+    },
+    CloseBlob {
+        hash: [u8; 32],
+    },
+    PutContentBatch {
+        entries: Vec<ContentEntry>,
+    },
+    DeleteContent {
+        path: String,
+    },
+    PutAccountPage {
+        path: String,
+        account: pubkey::Pubkey,
+        decoder: AccountDecoder,
+    },
+    UpdateName {
+        name: String,
+        program: pubkey::Pubkey,
+        default_page: String,
+    },
+    TransferName {
+        name: String,
+        owner: pubkey::Pubkey,
+    },
+    ClaimPage {
+        path: String,
+        authority: pubkey::Pubkey,
+    },
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct ContentEntry {
+    pub path: String,
+    pub body: Vec<u8>,
 }
 
 impl InstructionData {
@@ -97,60 +144,116 @@ impl InstructionData {
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
-struct ClientInfoYaml {
-    program: String,
-    network: String,
-    signer: String,
-    trunk_size: usize,
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub enum PageData {
+    RawData {
+        data: Vec<u8>,
+    },
+    TrunkPage {
+        trunks: u8,
+    },
+    BlobPage {
+        hash: [u8; 32],
+    },
+    AccountPage {
+        account: pubkey::Pubkey,
+        decoder: AccountDecoder,
+    },
 }
 
-const CONFIG_PATH: &str = "./w3-uploader.yaml";
+impl PageData {
+    /// Size of the key of the authority recorded after the page.
+    pub const AUTHORITY_LEN: usize = 32;
 
-pub struct ClientInfo {
-    pub program: Pubkey,
-    pub network: Network,
-    pub signer: Keypair,
-    pub trunk_size: usize,
-    pub loaded: bool,
+    /// Decodes the page of a page account, ignoring the key of the authority
+    /// recorded after it.
+    pub fn from_account_data(data: &[u8]) -> std::io::Result<Self> {
+        Self::deserialize(&mut &data[..])
+    }
+
+    /// Size of the page account holding this page.
+    pub fn account_len(&self) -> usize {
+        self.try_to_vec()
+            .map(|data| data.len() + Self::AUTHORITY_LEN)
+            .unwrap_or_default()
+    }
 }
 
-impl ClientInfo {
-    pub fn load() -> Self {
-        match fs::read(CONFIG_PATH) {
-            Ok(file_data) => {
-                let client_info_yaml: ClientInfoYaml = serde_yaml::from_slice(&file_data).unwrap();
-                return Self {
-                    program: Pubkey::from_str(&client_info_yaml.program).unwrap(),
-                    network: Network::from_string(&client_info_yaml.network),
-                    signer: Keypair::from_base58_string(&client_info_yaml.signer),
-                    trunk_size: client_info_yaml.trunk_size as usize,
-                    loaded: true,
-                };
-            }
-            Err(_) => {
-                return Self {
-                    program: Pubkey::default(),
-                    network: Network::Local,
-                    signer: Keypair::new(),
-                    trunk_size: 0,
-                    loaded: false,
-                };
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub enum AccountDecoder {
+    Raw,
+    BorshJson { schema: String },
+    ByteRange { offset: u32, length: u32 },
+}
+
+impl AccountDecoder {
+    /// Parses `raw`, `range:OFFSET:LENGTH` or `borsh:SCHEMA`.
+    pub fn from_string(decoder: &str) -> W3Result<Self> {
+        match decoder.split_once(':') {
+            None if decoder == "raw" => Ok(Self::Raw),
+            Some(("borsh", schema)) => {
+                crate::schema::parse_schema(schema)?;
+                Ok(Self::BorshJson {
+                    schema: schema.to_string(),
+                })
             }
+            Some(("range", range)) => match range.split_once(':') {
+                Some((offset, length)) => Ok(Self::ByteRange {
+                    offset: offset.parse().map_err(|e| {
+                        W3ClientError::InvalidArgument(format!("bad offset: {}", e))
+                    })?,
+                    length: length.parse().map_err(|e| {
+                        W3ClientError::InvalidArgument(format!("bad length: {}", e))
+                    })?,
+                }),
+                None => Err(W3ClientError::InvalidArgument(format!(
+                    "bad range decoder: {}",
+                    decoder
+                ))),
+            },
+            _ => Err(W3ClientError::InvalidArgument(format!(
+                "unknown decoder: {}",
+                decoder
+            ))),
         }
     }
-    pub fn get_w3_client(self) -> Result<W3Client, String> {
-        let ClientInfo {
-            program,
-            signer,
-            network,
-            loaded,
-            trunk_size,
-        } = self;
-        if loaded {
-            return Ok(W3Client::new(program, signer, network, trunk_size));
-        } else {
-            return Err("Client not loaded".into());
-        }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct BlobHeader {
+    pub hash: [u8; 32],
+    pub creator: pubkey::Pubkey,
+    pub ref_count: u64,
+    pub size: u32,
+    pub sealed: bool,
+}
+
+impl BlobHeader {
+    pub const LEN: usize = 32 + 32 + 8 + 4 + 1;
+    /// Largest blob the program accepts.
+    pub const MAX_SIZE: u32 = 1 << 20;
+
+    pub fn from_account_data(data: &[u8]) -> Option<Self> {
+        data.get(..Self::LEN)
+            .and_then(|header| Self::try_from_slice(header).ok())
     }
 }
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct NameConfig {
+    pub name: String,
+    /// Program whose PDAs hold the pages of this name.
+    pub program: pubkey::Pubkey,
+    pub creator: pubkey::Pubkey,
+    pub created_at: u64,
+    pub default_page: String,
+}
+
+/// Parses a base58 encoded keypair, unlike `Keypair::from_base58_string` it
+/// does not panic on bad input.
+pub fn keypair_from_base58(key: &str) -> W3Result<Keypair> {
+    let bytes = bs58::decode(key)
+        .into_vec()
+        .map_err(|e| W3ClientError::Key(e.to_string()))?;
+    Keypair::from_bytes(&bytes).map_err(|e| W3ClientError::Key(e.to_string()))
+}
@@ -0,0 +1,177 @@
+//! Pre-flight scan refusing to publish files that look like credentials.
+//!
+//! Uploaded content stays readable on a public chain forever, so files
+//! holding private keys, known token formats or high-entropy values assigned
+//! to secret-like keys block the upload. A file is uploaded anyway when it
+//! matches one of the `allow_secrets` globs.
+
+use {
+    regex::Regex,
+    std::{fs, sync::OnceLock},
+};
+
+use crate::{
+    client::W3Client,
+    error::{W3ClientError, W3Result},
+};
+
+/// Binary files are not scanned, a NUL byte in this prefix marks one.
+const BINARY_PROBE: usize = 8000;
+/// Minimum bits per character of a value assigned to a secret-like key.
+const MIN_ENTROPY: f64 = 3.5;
+const MIN_SECRET_LEN: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub web_path: String,
+    pub line: usize,
+    pub rule: &'static str,
+}
+
+struct Rule {
+    name: &'static str,
+    regex: Regex,
+}
+
+fn rules() -> &'static [Rule] {
+    static RULES: OnceLock<Vec<Rule>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        [
+            (
+                "private key",
+                r"-----BEGIN ([A-Z0-9]+ )*PRIVATE KEY( BLOCK)?-----",
+            ),
+            ("aws access key", r"\b(AKIA|ASIA)[0-9A-Z]{16}\b"),
+            (
+                "github token",
+                r"\b(gh[pousr]_[A-Za-z0-9]{36,}|github_pat_[A-Za-z0-9_]{60,})\b",
+            ),
+            ("slack token", r"\bxox[abprs]-[A-Za-z0-9-]{10,}"),
+            ("stripe key", r"\b[rs]k_live_[A-Za-z0-9]{20,}"),
+            ("google api key", r"\bAIza[0-9A-Za-z_-]{35}"),
+            ("api secret key", r"\bsk-(ant-|proj-)?[A-Za-z0-9_-]{32,}"),
+        ]
+        .into_iter()
+        .map(|(name, regex)| Rule {
+            name,
+            regex: Regex::new(regex).unwrap(),
+        })
+        .collect()
+    })
+}
+
+fn keypair_array() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"\[\s*(\d{1,3}\s*,\s*){63}\d{1,3}\s*\]").unwrap())
+}
+
+fn secret_assignment() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(
+            r#"(?i)(secret|token|passw(or)?d|api[_-]?key|private[_-]?key|access[_-]?key)[A-Za-z0-9_-]*["']?\s*[:=]\s*["']?([A-Za-z0-9+/=_.-]{20,})"#,
+        )
+        .unwrap()
+    })
+}
+
+/// Shannon entropy of `text` in bits per character.
+pub fn entropy(text: &str) -> f64 {
+    let mut counts = [0usize; 256];
+    for byte in text.bytes() {
+        counts[byte as usize] += 1;
+    }
+    let len = text.len() as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Lines of `data` that look like secrets with the rule they match, at most
+/// one per rule.
+pub fn scan(data: &[u8]) -> Vec<(usize, &'static str)> {
+    if data[..data.len().min(BINARY_PROBE)].contains(&0) {
+        return Vec::new();
+    }
+    let text = String::from_utf8_lossy(data);
+    let line_of = |offset: usize| text[..offset].matches('\n').count() + 1;
+
+    let mut findings = Vec::new();
+    for rule in rules() {
+        if let Some(found) = rule.regex.find(&text) {
+            findings.push((line_of(found.start()), rule.name));
+        }
+    }
+    let keypair = keypair_array().find_iter(&text).find(|found| {
+        found
+            .as_str()
+            .trim_matches(|c| c == '[' || c == ']')
+            .split(',')
+            .all(|byte| byte.trim().parse::<u8>().is_ok())
+    });
+    if let Some(found) = keypair {
+        findings.push((line_of(found.start()), "solana keypair"));
+    }
+    let assignment = secret_assignment().captures_iter(&text).find(|captures| {
+        let value = &captures[3];
+        value.len() >= MIN_SECRET_LEN && entropy(value) >= MIN_ENTROPY
+    });
+    if let Some(captures) = assignment {
+        findings.push((
+            line_of(captures.get(0).unwrap().start()),
+            "high-entropy secret",
+        ));
+    }
+    findings.sort();
+    findings
+}
+
+impl W3Client {
+    /// Refuses to go on when one of `files` looks like it holds a secret and
+    /// is not allowed by `allow_secrets`.
+    pub fn check_secrets(&self, files: &[(String, String)]) -> W3Result<()> {
+        let mut findings = Vec::new();
+        for (web_path, full_path) in files {
+            let relative = web_path.trim_start_matches('/');
+            let found = scan(&fs::read(full_path)?);
+            if found.is_empty() {
+                continue;
+            }
+            if self
+                .allow_secrets
+                .iter()
+                .any(|pattern| pattern.matches(relative, false))
+            {
+                log::warn!("Uploading {} despite possible secrets", web_path);
+                continue;
+            }
+            findings.extend(found.into_iter().map(|(line, rule)| Finding {
+                web_path: web_path.clone(),
+                line,
+                rule,
+            }));
+        }
+        if findings.is_empty() {
+            return Ok(());
+        }
+        for finding in &findings {
+            log::error!(
+                "Possible {} in {} line {}",
+                finding.rule,
+                finding.web_path,
+                finding.line
+            );
+        }
+        let mut paths: Vec<&str> = findings.iter().map(|f| f.web_path.as_str()).collect();
+        paths.dedup();
+        Err(W3ClientError::Secret(format!(
+            "{}, remove them or allow them with --allow-secret",
+            paths.join(", ")
+        )))
+    }
+}
@@ -0,0 +1,315 @@
+//! Where the signing keys come from.
+//!
+//! A signer source is one of
+//! - `external:COMMAND ARGS...`, a process signing over stdin/stdout,
+//! - the path of a Solana CLI keypair file, e.g. `~/.config/solana/id.json`,
+//! - the path of an encrypted keystore, see the `keystore` module,
+//! - a base58 encoded keypair.
+//!
+//! Without an explicit source the authority is read from `W3_KEYPAIR`, then
+//! the `keypair_path` of the Solana CLI config, then `~/.config/solana/id.json`.
+//!
+//! External signers speak line-delimited JSON-RPC 2.0: `get_pubkey` returns
+//! the base58 pubkey and `sign_message` with `{"message": <base58>}` returns
+//! the base58 signature. Responses are matched to requests by their `id`, a
+//! request left unanswered for `EXTERNAL_TIMEOUT` fails.
+
+use {
+    log::{info, warn},
+    serde::Deserialize,
+    serde_json::{json, Value},
+    solana_sdk::{
+        bs58,
+        pubkey::Pubkey,
+        signature::{read_keypair_file, Keypair, Signature},
+        signer::{Signer, SignerError},
+    },
+    std::{
+        collections::HashMap,
+        env, fs,
+        io::{BufRead, BufReader, Write},
+        path::{Path, PathBuf},
+        process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+        str::FromStr,
+        sync::{
+            mpsc::{channel, RecvTimeoutError, Sender},
+            Arc, Mutex,
+        },
+        thread,
+        time::Duration,
+    },
+};
+
+use crate::{
+    error::{W3ClientError, W3Result},
+    keystore::{unlock_passphrase, Keystore, PassphraseSource},
+    sdk::keypair_from_base58,
+};
+
+/// Signer shared by the upload threads.
+pub type BoxedSigner = Box<dyn Signer + Send + Sync>;
+
+pub const KEYPAIR_ENV: &str = "W3_KEYPAIR";
+pub const PAYER_ENV: &str = "W3_PAYER";
+
+const EXTERNAL_PREFIX: &str = "external:";
+
+/// Builds the signer described by `source`.
+pub fn signer_from_source(source: &str) -> W3Result<BoxedSigner> {
+    let source = source.trim();
+    if let Some(command) = source.strip_prefix(EXTERNAL_PREFIX) {
+        return Ok(Box::new(ExternalSigner::spawn(command)?));
+    }
+    Ok(Box::new(keypair_from_source(source)?))
+}
+
+/// Reads the keypair of a keystore, keypair file or base58 source.
+pub fn keypair_from_source(source: &str) -> W3Result<Keypair> {
+    keypair_from_source_with(source, &PassphraseSource::Terminal)
+}
+
+/// `keypair_from_source` unlocking keystores with `passphrase`.
+pub fn keypair_from_source_with(source: &str, passphrase: &PassphraseSource) -> W3Result<Keypair> {
+    let source = source.trim();
+    if source.starts_with(EXTERNAL_PREFIX) {
+        return Err(W3ClientError::Key(
+            "the key of an external signer cannot be read".into(),
+        ));
+    }
+    let path = expand_home(source);
+    if path.is_file() {
+        if let Some(keystore) = Keystore::from_slice(&fs::read(&path)?) {
+            let keystore =
+                keystore.map_err(|e| W3ClientError::Key(format!("{}: {}", path.display(), e)))?;
+            return keystore.decrypt(&unlock_passphrase(&path, passphrase)?);
+        }
+        return read_keypair_file(&path)
+            .map_err(|e| W3ClientError::Key(format!("{}: {}", path.display(), e)));
+    }
+    keypair_from_base58(source)
+}
+
+/// Content authority: `W3_KEYPAIR`, then `configured` when not empty, then
+/// the Solana CLI keypair.
+pub fn resolve_signer(configured: &str) -> W3Result<BoxedSigner> {
+    if let Ok(source) = env::var(KEYPAIR_ENV) {
+        return signer_from_source(&source);
+    }
+    if !configured.is_empty() {
+        return signer_from_source(configured);
+    }
+    let path = solana_cli_keypair_path().ok_or_else(|| {
+        W3ClientError::Key(format!(
+            "no signer configured, set {} or create a Solana CLI keypair",
+            KEYPAIR_ENV
+        ))
+    })?;
+    info!("Using Solana CLI keypair {}", path.display());
+    signer_from_source(&path.to_string_lossy())
+}
+
+/// Fee payer: `W3_PAYER`, then `configured`. `None` lets the authority pay.
+pub fn resolve_payer(configured: Option<&str>) -> W3Result<Option<BoxedSigner>> {
+    match env::var(PAYER_ENV).ok().as_deref().or(configured) {
+        Some(source) if !source.is_empty() => signer_from_source(source).map(Some),
+        _ => Ok(None),
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[derive(Deserialize)]
+struct SolanaCliConfig {
+    keypair_path: Option<String>,
+}
+
+/// Keypair of the Solana CLI config, or its default `id.json`.
+fn solana_cli_keypair_path() -> Option<PathBuf> {
+    let config_dir = home_dir()?.join(".config").join("solana");
+    let configured = fs::read(config_dir.join("cli").join("config.yml"))
+        .ok()
+        .and_then(|data| serde_yaml::from_slice::<SolanaCliConfig>(&data).ok())
+        .and_then(|config| config.keypair_path)
+        .map(|path| expand_home(&path));
+    [configured, Some(config_dir.join("id.json"))]
+        .into_iter()
+        .flatten()
+        .find(|path| Path::new(path).is_file())
+}
+
+/// Time an external signer has to answer a request.
+pub const EXTERNAL_TIMEOUT: Duration = Duration::from_secs(120);
+
+struct ExternalProcess {
+    child: Child,
+    stdin: ChildStdin,
+    next_id: u64,
+}
+
+/// Requests waiting for their response, by id.
+type PendingRequests = Arc<Mutex<HashMap<u64, Sender<Value>>>>;
+
+/// Signer delegating to a local process, the private key never enters the
+/// uploader.
+pub struct ExternalSigner {
+    pubkey: Pubkey,
+    process: Mutex<ExternalProcess>,
+    pending: PendingRequests,
+    timeout: Duration,
+}
+
+impl ExternalSigner {
+    pub fn spawn(command: &str) -> W3Result<Self> {
+        Self::spawn_with_timeout(command, EXTERNAL_TIMEOUT)
+    }
+
+    pub fn spawn_with_timeout(command: &str, timeout: Duration) -> W3Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| W3ClientError::Key("empty external signer command".into()))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let pending = PendingRequests::default();
+        let responses = pending.clone();
+        thread::spawn(move || Self::read_responses(stdout, responses));
+        let mut signer = Self {
+            pubkey: Pubkey::default(),
+            process: Mutex::new(ExternalProcess {
+                child,
+                stdin,
+                next_id: 0,
+            }),
+            pending,
+            timeout,
+        };
+
+        let result = signer
+            .call("get_pubkey", Value::Null)
+            .map_err(|e| W3ClientError::Key(e.to_string()))?;
+        signer.pubkey = result
+            .as_str()
+            .and_then(|pubkey| Pubkey::from_str(pubkey).ok())
+            .ok_or_else(|| W3ClientError::Key(format!("bad external pubkey: {}", result)))?;
+        info!("External signer {} for {}", program, signer.pubkey);
+        Ok(signer)
+    }
+
+    /// Hands each response to the request with its id, until the process
+    /// closes its output.
+    fn read_responses(stdout: ChildStdout, pending: PendingRequests) {
+        for line in BufReader::new(stdout).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let response: Value = match serde_json::from_str(&line) {
+                Ok(response) => response,
+                Err(e) => {
+                    warn!("Ignoring external signer output {:?}: {}", line, e);
+                    continue;
+                }
+            };
+            let request = response["id"]
+                .as_u64()
+                .and_then(|id| pending.lock().unwrap().remove(&id));
+            match request {
+                Some(request) => {
+                    let _ = request.send(response);
+                }
+                None => warn!(
+                    "External signer answered unknown request {}",
+                    response["id"]
+                ),
+            }
+        }
+        // dropping the senders fails the requests still waiting
+        pending.lock().unwrap().clear();
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value, SignerError> {
+        let (sender, receiver) = channel();
+        let id = {
+            let mut process = self.process.lock().unwrap();
+            process.next_id += 1;
+            let id = process.next_id;
+            self.pending.lock().unwrap().insert(id, sender);
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": method,
+                "params": params,
+            });
+            let sent = writeln!(process.stdin, "{}", request).and_then(|_| process.stdin.flush());
+            if let Err(e) = sent {
+                self.pending.lock().unwrap().remove(&id);
+                return Err(SignerError::Connection(e.to_string()));
+            }
+            id
+        };
+
+        let mut response = match receiver.recv_timeout(self.timeout) {
+            Ok(response) => response,
+            Err(RecvTimeoutError::Timeout) => {
+                self.pending.lock().unwrap().remove(&id);
+                return Err(SignerError::Connection(format!(
+                    "external signer did not answer {} within {:?}",
+                    method, self.timeout
+                )));
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(SignerError::Connection("external signer exited".into()))
+            }
+        };
+        if let Some(error) = response.get("error") {
+            return Err(SignerError::Custom(error.to_string()));
+        }
+        Ok(response["result"].take())
+    }
+}
+
+impl Signer for ExternalSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let result = self.call(
+            "sign_message",
+            json!({ "message": bs58::encode(message).into_string() }),
+        )?;
+        result
+            .as_str()
+            .and_then(|signature| Signature::from_str(signature).ok())
+            .ok_or_else(|| SignerError::Protocol(format!("bad signature: {}", result)))
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+impl Drop for ExternalSigner {
+    fn drop(&mut self) {
+        if let Ok(process) = self.process.get_mut() {
+            let _ = process.child.kill();
+            let _ = process.child.wait();
+        }
+    }
+}
@@ -1,56 +1,221 @@
 use {
-    log::{error, info},
+    log::{error, info, warn},
+    solana_client::rpc_client::RpcClient,
     solana_program::pubkey::Pubkey,
-    solana_sdk::{instruction::Instruction, signature::Keypair, transaction::Transaction},
+    solana_sdk::{
+        address_lookup_table::AddressLookupTableAccount,
+        hash::Hash,
+        instruction::Instruction,
+        message::{v0, Message, VersionedMessage},
+        signature::Signature,
+        signer::Signer,
+        transaction::{TransactionError, VersionedTransaction},
+    },
+    std::{
+        sync::Mutex,
+        thread,
+        time::{Duration, Instant},
+    },
 };
 
-use crate::client::W3Client;
+use crate::{
+    client::W3Client,
+    error::{W3ClientError, W3Result},
+    packer,
+};
+
+/// A blockhash stays valid for about a minute, refresh well before that.
+const BLOCKHASH_TTL: Duration = Duration::from_secs(30);
+
+/// Latest blockhash shared by every transaction sent concurrently.
+#[derive(Default)]
+pub struct BlockhashCache {
+    latest: Mutex<Option<(Hash, Instant)>>,
+}
+
+impl BlockhashCache {
+    pub fn get(&self, connection: &RpcClient) -> W3Result<Hash> {
+        let mut latest = self.latest.lock().unwrap();
+        match *latest {
+            Some((blockhash, fetched_at)) if fetched_at.elapsed() < BLOCKHASH_TTL => Ok(blockhash),
+            _ => {
+                let blockhash = connection.get_latest_blockhash()?;
+                *latest = Some((blockhash, Instant::now()));
+                Ok(blockhash)
+            }
+        }
+    }
+
+    pub fn invalidate(&self) {
+        *self.latest.lock().unwrap() = None;
+    }
+}
+
+/// How a transaction is sent again after a transient failure, waiting
+/// `backoff` before the first retry and twice as long after each one.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub retries: usize,
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 5,
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn delay(&self, retry: usize) -> Duration {
+        let factor = 1u32 << retry.saturating_sub(1).min(16);
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Message carrying `instructions`: a legacy one when it fits a packet or
+/// no lookup table is given, a v0 one looking up the accounts of `tables`
+/// otherwise.
+pub fn compile_message(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    tables: &[AddressLookupTableAccount],
+    blockhash: Hash,
+) -> W3Result<VersionedMessage> {
+    if tables.is_empty() || packer::fits_in_packet(instructions, payer, 0) {
+        return Ok(VersionedMessage::Legacy(Message::new_with_blockhash(
+            instructions,
+            Some(payer),
+            &blockhash,
+        )));
+    }
+    v0::Message::try_compile(payer, instructions, tables, blockhash)
+        .map(VersionedMessage::V0)
+        .map_err(|e| W3ClientError::InvalidArgument(format!("cannot compile v0 message: {}", e)))
+}
+
 pub trait SolanaTransaction {
-    fn send_instruction(&self, payer: &Pubkey, singers: &Vec<&Keypair>, instruction: Instruction);
+    fn send_instruction(
+        &self,
+        payer: &Pubkey,
+        singers: &[&dyn Signer],
+        instruction: Instruction,
+    ) -> W3Result<Signature>;
     fn send_instructions(
         &self,
         payer: &Pubkey,
-        singers: &Vec<&Keypair>,
+        singers: &[&dyn Signer],
         instructions: Vec<Instruction>,
-    );
-    fn get_account_info(&self, pubkey: &Pubkey) -> Option<solana_sdk::account::Account>;
+    ) -> W3Result<Signature>;
+    fn send_transaction(
+        &self,
+        payer: &Pubkey,
+        singers: &[&dyn Signer],
+        instructions: &[Instruction],
+    ) -> W3Result<Signature>;
+    /// `None` when the account does not exist.
+    fn get_account_info(&self, pubkey: &Pubkey) -> W3Result<Option<solana_sdk::account::Account>>;
 }
 
 impl SolanaTransaction for W3Client {
-    fn send_instructions(
+    fn send_transaction(
         &self,
         payer: &Pubkey,
-        singers: &Vec<&Keypair>,
-        instructions: Vec<Instruction>,
-    ) {
-        info!("instruction data len : {:?}", instructions.len());
-        let blockhash = self.connection.get_latest_blockhash().unwrap();
+        singers: &[&dyn Signer],
+        instructions: &[Instruction],
+    ) -> W3Result<Signature> {
+        let instructions = self.with_compute_budget(payer, instructions)?;
+        let sign = |blockhash| -> W3Result<VersionedTransaction> {
+            let message = compile_message(payer, &instructions, &self.lookup_tables, blockhash)?;
+            Ok(VersionedTransaction::try_new(message, singers)?)
+        };
+        let mut blockhash = self.blockhash.get(&self.connection)?;
+        let mut transaction = sign(blockhash)?;
+
+        // a send timing out does not mean the transaction did not land
+        let landed = |signature: &Signature| match self.connection.get_signature_status(signature) {
+            Ok(Some(status)) => Some(
+                status
+                    .map(|_| *signature)
+                    .map_err(W3ClientError::Transaction),
+            ),
+            _ => None,
+        };
 
-        let transaction =
-            Transaction::new_signed_with_payer(&instructions, Some(&payer), singers, blockhash);
+        let mut retry = 0;
+        loop {
+            let signature = transaction.signatures[0];
+            let error = match self.connection.send_and_confirm_transaction(&transaction) {
+                Ok(signature) => return Ok(signature),
+                Err(e) => e,
+            };
+            match error.get_transaction_error() {
+                Some(TransactionError::AlreadyProcessed) => return Ok(signature),
+                Some(TransactionError::BlockhashNotFound) | None => {}
+                // the program rejected it, sending it again cannot help
+                Some(_) => return Err(error.into()),
+            }
+            if let Some(result) = landed(&signature) {
+                return result;
+            }
+            if retry == self.retry.retries {
+                return Err(error.into());
+            }
 
-        match self.connection.send_and_confirm_transaction(&transaction) {
-            Ok(tx) => {
-                info!("send transaction tx : {:?}", tx);
+            retry += 1;
+            let delay = self.retry.delay(retry);
+            warn!(
+                "send transaction error : {}, retry {}/{} in {:?}",
+                error, retry, self.retry.retries, delay
+            );
+            thread::sleep(delay);
+            if let Some(result) = landed(&signature) {
+                return result;
             }
-            Err(e) => {
-                error!("send transaction error : {:?}", e);
+
+            let valid = self
+                .connection
+                .is_blockhash_valid(&blockhash, self.connection.commitment())
+                .unwrap_or(false);
+            if !valid {
+                // the transaction can no longer land, sign it with a new blockhash
+                self.blockhash.invalidate();
+                blockhash = self.blockhash.get(&self.connection)?;
+                transaction = sign(blockhash)?;
             }
         }
     }
 
-    fn send_instruction(&self, payer: &Pubkey, singers: &Vec<&Keypair>, instruction: Instruction) {
+    fn send_instructions(
+        &self,
+        payer: &Pubkey,
+        singers: &[&dyn Signer],
+        instructions: Vec<Instruction>,
+    ) -> W3Result<Signature> {
+        info!("instruction data len : {:?}", instructions.len());
+        self.send_transaction(payer, singers, &instructions)
+            .inspect(|tx| info!("send transaction tx : {:?}", tx))
+            .inspect_err(|e| error!("send transaction error : {}", e))
+    }
+
+    fn send_instruction(
+        &self,
+        payer: &Pubkey,
+        singers: &[&dyn Signer],
+        instruction: Instruction,
+    ) -> W3Result<Signature> {
         info!("instruction data len : {:?}", instruction.data.len());
         self.send_instructions(payer, singers, vec![instruction])
     }
 
-    fn get_account_info(&self, pubkey: &Pubkey) -> Option<solana_sdk::account::Account> {
-        match self.connection.get_account(pubkey) {
-            Ok(account) => Some(account),
-            Err(e) => {
-                error!("get account error : {:?}", e);
-                None
-            }
-        }
+    fn get_account_info(&self, pubkey: &Pubkey) -> W3Result<Option<solana_sdk::account::Account>> {
+        Ok(self
+            .connection
+            .get_account_with_commitment(pubkey, self.connection.commitment())?
+            .value)
     }
 }
@@ -0,0 +1,251 @@
+use {
+    log::{error, info, warn},
+    serde::{Deserialize, Serialize},
+    solana_sdk::hash::{hash, Hash},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fs,
+        path::Path,
+    },
+};
+
+use crate::{
+    client::W3Client,
+    error::{W3ClientError, W3Result},
+    sdk::PageData,
+};
+
+/// Page listing every uploaded file, dotfiles are never uploaded so it cannot
+/// collide with a site path.
+pub const MANIFEST_PATH: &str = "/.w3-manifest.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Manifest {
+    pub files: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    /// Sorts the `local` files of a site by what `remote` lists for them and
+    /// returns the manifest of the site once every upload succeeded. Files
+    /// only listed remotely are removed, and stay listed unless `prune`.
+    pub fn diff(
+        local: &BTreeMap<String, ManifestEntry>,
+        remote: &Manifest,
+        prune: bool,
+    ) -> (SyncSummary, Manifest) {
+        let mut summary = SyncSummary::default();
+        let mut manifest = Manifest::default();
+        for (web_path, entry) in local {
+            match remote.files.get(web_path) {
+                Some(current) if current == entry => summary.unchanged.push(web_path.clone()),
+                Some(_) => summary.changed.push(web_path.clone()),
+                None => summary.added.push(web_path.clone()),
+            }
+            manifest.files.insert(web_path.clone(), entry.clone());
+        }
+        for (web_path, entry) in &remote.files {
+            if !local.contains_key(web_path) {
+                summary.removed.push(web_path.clone());
+                if !prune {
+                    manifest.files.insert(web_path.clone(), entry.clone());
+                }
+            }
+        }
+        (summary, manifest)
+    }
+
+    /// Lists the `written` files as they were uploaded and drops the
+    /// `dropped` ones, deleted or left in an unknown state by a failed upload.
+    pub fn update(&mut self, written: &BTreeMap<String, ManifestEntry>, dropped: &[String]) {
+        self.files.extend(
+            written
+                .iter()
+                .map(|(path, entry)| (path.clone(), entry.clone())),
+        );
+        for web_path in dropped {
+            self.files.remove(web_path);
+        }
+    }
+
+    /// Lists `web_path` as `remote` does, for a path left as it was on chain
+    /// by a failed upload or delete.
+    pub fn restore(&mut self, remote: &Manifest, web_path: &str) {
+        match remote.files.get(web_path) {
+            Some(entry) => self.files.insert(web_path.to_string(), entry.clone()),
+            None => self.files.remove(web_path),
+        };
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SyncSummary {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+impl SyncSummary {
+    pub fn print(&self) {
+        for (label, paths) in [
+            ("added", &self.added),
+            ("changed", &self.changed),
+            ("removed", &self.removed),
+            ("failed", &self.failed),
+        ] {
+            for path in paths {
+                info!("{:>9}: {}", label, path);
+            }
+        }
+        info!(
+            "{} added, {} changed, {} unchanged, {} removed, {} failed",
+            self.added.len(),
+            self.changed.len(),
+            self.unchanged.len(),
+            self.removed.len(),
+            self.failed.len()
+        );
+    }
+}
+
+pub fn manifest_entry(data: &[u8]) -> ManifestEntry {
+    ManifestEntry {
+        size: data.len() as u64,
+        sha256: hash(data).to_string(),
+    }
+}
+
+impl W3Client {
+    pub fn get_manifest(&self) -> W3Result<Option<Manifest>> {
+        let data = match self.get_page(MANIFEST_PATH)? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        match serde_json::from_slice(&data) {
+            Ok(manifest) => Ok(Some(manifest)),
+            Err(e) => {
+                warn!("Ignoring unreadable site manifest: {:?}", e);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Keeps the site manifest in step with uploads and deletes made outside
+    /// `sync`, which would otherwise skip files by their stale entries.
+    /// Without a manifest there is nothing to do, sync compares every page.
+    pub fn update_manifest(
+        &self,
+        written: &BTreeMap<String, ManifestEntry>,
+        dropped: &[String],
+    ) -> W3Result<()> {
+        if dropped.iter().any(|web_path| web_path == MANIFEST_PATH) {
+            return Ok(());
+        }
+        let Some(mut manifest) = self.get_manifest()? else {
+            return Ok(());
+        };
+        manifest.update(written, dropped);
+        self.write_manifest(&manifest)
+    }
+
+    fn write_manifest(&self, manifest: &Manifest) -> W3Result<()> {
+        let manifest_data = serde_json::to_vec_pretty(manifest)?;
+        let report = self.run_jobs(vec![self.content_job(MANIFEST_PATH, manifest_data)?]);
+        if report.failed.is_empty() {
+            return Ok(());
+        }
+        let reason = report
+            .errors
+            .get(MANIFEST_PATH)
+            .cloned()
+            .unwrap_or_default();
+        Err(W3ClientError::Upload(format!(
+            "{}: {}",
+            MANIFEST_PATH, reason
+        )))
+    }
+
+    /// On-chain state of `web_path` without its manifest, blob pages are
+    /// compared by hash and never downloaded.
+    fn remote_entry(&self, web_path: &str) -> W3Result<Option<ManifestEntry>> {
+        match self.get_page_data(web_path)? {
+            Some(PageData::BlobPage { hash }) => {
+                Ok(self.get_blob_header(&hash)?.map(|header| ManifestEntry {
+                    size: header.size as u64,
+                    sha256: Hash::new_from_array(hash).to_string(),
+                }))
+            }
+            Some(_) => Ok(self.get_page(web_path)?.map(|data| manifest_entry(&data))),
+            None => Ok(None),
+        }
+    }
+
+    /// Uploads the files of `dir` that are new or differ from the chain, then
+    /// records the site state in the manifest page. Removed files are deleted
+    /// from chain only with `prune`.
+    pub fn sync(&self, dir: &Path, prune: bool) -> W3Result<SyncSummary> {
+        let mut files = Vec::new();
+        self.collect_files(dir, dir, &mut files)?;
+        let mut local = BTreeMap::new();
+        for (web_path, full_path) in &files {
+            local.insert(web_path.clone(), manifest_entry(&fs::read(full_path)?));
+        }
+
+        let remote = match self.get_manifest()? {
+            Some(manifest) => {
+                info!("Site manifest lists {} files", manifest.files.len());
+                manifest
+            }
+            None => {
+                info!("No site manifest, comparing every page");
+                let mut manifest = Manifest::default();
+                for web_path in local.keys() {
+                    if let Some(entry) = self.remote_entry(web_path)? {
+                        manifest.files.insert(web_path.clone(), entry);
+                    }
+                }
+                manifest
+            }
+        };
+
+        let (mut summary, mut manifest) = Manifest::diff(&local, &remote, prune);
+        let pending: BTreeSet<&String> = summary.added.iter().chain(&summary.changed).collect();
+        let uploads: Vec<(String, String)> = files
+            .iter()
+            .filter(|(web_path, _)| pending.contains(web_path))
+            .cloned()
+            .collect();
+
+        self.check_secrets(&uploads)?;
+        let report = self.run_journaled_jobs(self.upload_jobs(&uploads)?, dir)?;
+        for web_path in report.failed {
+            manifest.restore(&remote, &web_path);
+            summary.failed.push(web_path);
+        }
+
+        if prune {
+            for web_path in &summary.removed {
+                if let Err(e) = self.delete_content(web_path) {
+                    error!("Failed to delete {} : {}", web_path, e);
+                    manifest.restore(&remote, web_path);
+                    summary.failed.push(web_path.clone());
+                }
+            }
+        }
+
+        if let Err(e) = self.write_manifest(&manifest) {
+            summary.failed.push(MANIFEST_PATH.to_string());
+            summary.print();
+            return Err(e);
+        }
+
+        Ok(summary)
+    }
+}
@@ -0,0 +1,93 @@
+use {
+    log::{info, warn},
+    serde::Serialize,
+    std::{collections::BTreeSet, fs, path::Path},
+};
+
+use crate::{client::W3Client, error::W3Result, reader::PageContent, sync::MANIFEST_PATH};
+
+/// Outcome of comparing a local build with the content served on chain.
+#[derive(Serialize, Debug, Default)]
+pub struct VerifyReport {
+    pub matched: Vec<String>,
+    /// Local files with no page on chain.
+    pub missing: Vec<String>,
+    /// Pages whose content differs from the local file.
+    pub mismatched: Vec<String>,
+    /// Pages listed in the site manifest with no local file.
+    pub extra: Vec<String>,
+    /// Pages with missing trunks or an unsealed blob.
+    pub incomplete: Vec<String>,
+}
+
+impl VerifyReport {
+    /// Files `web_path` under the outcome of comparing `local` with `remote`.
+    pub fn record(&mut self, web_path: String, local: &[u8], remote: &PageContent) {
+        match remote {
+            PageContent::Missing => self.missing.push(web_path),
+            PageContent::Incomplete(reason) => {
+                warn!("{} is incomplete: {}", web_path, reason);
+                self.incomplete.push(web_path)
+            }
+            PageContent::Complete(data) if data == local => self.matched.push(web_path),
+            PageContent::Complete(_) => self.mismatched.push(web_path),
+        }
+    }
+
+    /// No difference between the local build and the chain.
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty()
+            && self.mismatched.is_empty()
+            && self.extra.is_empty()
+            && self.incomplete.is_empty()
+    }
+
+    pub fn print(&self) {
+        for (label, paths) in [
+            ("missing", &self.missing),
+            ("mismatched", &self.mismatched),
+            ("extra", &self.extra),
+            ("incomplete", &self.incomplete),
+        ] {
+            for path in paths {
+                warn!("{:>10}: {}", label, path);
+            }
+        }
+        info!(
+            "{} matched, {} missing, {} mismatched, {} extra, {} incomplete",
+            self.matched.len(),
+            self.missing.len(),
+            self.mismatched.len(),
+            self.extra.len(),
+            self.incomplete.len()
+        );
+    }
+}
+
+impl W3Client {
+    /// Compares every file under `dir` byte for byte with the content served
+    /// on chain. Pages of the site manifest missing from `dir` are extra.
+    pub fn verify(&self, dir: &Path) -> W3Result<VerifyReport> {
+        let mut files = Vec::new();
+        self.collect_files(dir, dir, &mut files)?;
+        files.sort();
+
+        let mut report = VerifyReport::default();
+        let web_paths: Vec<String> = files.iter().map(|(web_path, _)| web_path.clone()).collect();
+        let pages = self.fetch_pages(&web_paths)?;
+        for ((web_path, full_path), page) in files.into_iter().zip(pages) {
+            let local = fs::read(&full_path)?;
+            report.record(web_path, &local, &page);
+        }
+
+        if let Some(manifest) = self.get_manifest()? {
+            let local: BTreeSet<&String> = web_paths.iter().collect();
+            report.extra = manifest
+                .files
+                .into_keys()
+                .filter(|web_path| web_path != MANIFEST_PATH && !local.contains(web_path))
+                .collect();
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,123 @@
+//! Re-deploys a directory whenever its files change, meant for a local
+//! `solana-test-validator`.
+//!
+//! The directory is polled, which needs no platform support and sees the
+//! same files as an upload: dotfiles, ignored and excluded files never
+//! trigger a deploy. Once a change is seen the files must stay unchanged for
+//! the debounce delay, so a build writing many files deploys once.
+
+use {
+    log::{error, info, warn},
+    std::{
+        collections::BTreeMap,
+        fs,
+        path::Path,
+        thread,
+        time::{Duration, SystemTime},
+    },
+};
+
+use crate::{client::W3Client, error::W3Result, sdk::Network};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Modification time and size of every file to upload, by web path.
+pub type Snapshot = BTreeMap<String, (Option<SystemTime>, u64)>;
+
+/// Web paths added, modified or removed between two snapshots.
+pub fn changed_paths(old: &Snapshot, new: &Snapshot) -> Vec<String> {
+    let mut changed: Vec<String> = new
+        .iter()
+        .filter(|(web_path, state)| old.get(*web_path) != Some(state))
+        .map(|(web_path, _)| web_path.clone())
+        .collect();
+    changed.extend(
+        old.keys()
+            .filter(|web_path| !new.contains_key(*web_path))
+            .cloned(),
+    );
+    changed.sort();
+    changed
+}
+
+impl W3Client {
+    pub fn snapshot(&self, dir: &Path) -> W3Result<Snapshot> {
+        let mut files = Vec::new();
+        self.collect_files(dir, dir, &mut files)?;
+        let mut snapshot = Snapshot::new();
+        for (web_path, full_path) in files {
+            // a file deleted since the walk is simply left out
+            if let Ok(metadata) = fs::metadata(&full_path) {
+                snapshot.insert(web_path, (metadata.modified().ok(), metadata.len()));
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Syncs `dir` now and after each change, until the process is stopped.
+    /// Failed deploys are logged and retried on the next change.
+    pub fn watch(&self, dir: &Path, prune: bool, debounce: Duration) -> W3Result<()> {
+        if !matches!(self.network, Network::Local) {
+            warn!(
+                "Watching {} against {}, every change costs rent and fees",
+                dir.display(),
+                self.network
+            );
+        }
+        let mut last = self.snapshot(dir)?;
+        self.deploy_changes(dir, prune);
+        info!("Watching {} for changes", dir.display());
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let mut current = match self.watched_snapshot(dir) {
+                Some(snapshot) => snapshot,
+                None => continue,
+            };
+            if changed_paths(&last, &current).is_empty() {
+                continue;
+            }
+            // wait for the writes to settle
+            loop {
+                thread::sleep(debounce);
+                match self.watched_snapshot(dir) {
+                    Some(settled) if settled == current => break,
+                    Some(settled) => current = settled,
+                    None => {}
+                }
+            }
+            for web_path in changed_paths(&last, &current) {
+                info!("Changed: {}", web_path);
+            }
+            last = current;
+            self.deploy_changes(dir, prune);
+            info!("Watching {} for changes", dir.display());
+        }
+    }
+
+    /// Snapshot of `dir`, `None` while a build has removed it or a file
+    /// vanishes during the walk.
+    fn watched_snapshot(&self, dir: &Path) -> Option<Snapshot> {
+        if !dir.is_dir() {
+            return None;
+        }
+        match self.snapshot(dir) {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                warn!("Cannot read {}: {}", dir.display(), e);
+                None
+            }
+        }
+    }
+
+    fn deploy_changes(&self, dir: &Path, prune: bool) {
+        match self.sync(dir, prune) {
+            Ok(summary) => {
+                summary.print();
+                if !summary.failed.is_empty() {
+                    error!("Deploy incomplete, it is retried on the next change");
+                }
+            }
+            Err(e) => error!("Deploy failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Helpers for other programs publishing pages through the w3 program.
+//!
+//! Every write is paid by an `authority` account which must sign. A program
+//! signs for its own PDA by passing the PDA seeds as `signer_seeds`; such an
+//! authority has to stay owned by the system program and hold enough lamports
+//! to pay the rent of the accounts it creates. A page records the authority
+//! that wrote it, only that authority may rewrite or delete it afterwards.
+//!
+//! Depend on this crate with the `no-entrypoint` feature to use it from
+//! another program.
+
+use {
+    crate::instruction::InstructionData,
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
+        program::invoke_signed,
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        system_program,
+    },
+    w3solana::pda_helper::PdaHelper,
+};
+
+pub fn put_content_instruction(
+    w3_program: &Pubkey,
+    authority: &Pubkey,
+    path: &str,
+    body: &[u8],
+) -> Instruction {
+    let (content_account, _) = PdaHelper::new(*w3_program).find_program_address_by_text(path);
+    Instruction {
+        program_id: *w3_program,
+        accounts: vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(content_account, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: InstructionData::PutContent {
+            path: path.to_string(),
+            body: body.to_vec(),
+        }
+        .to_bytes(),
+    }
+}
+
+/// `trunks` is the `TrunkPage.trunks` of a chunked page, the number of its
+/// last trunk, and `None` for other pages.
+pub fn delete_content_instruction(
+    w3_program: &Pubkey,
+    authority: &Pubkey,
+    path: &str,
+    trunks: Option<u8>,
+) -> Instruction {
+    let pda_helper = PdaHelper::new(*w3_program);
+    let (page_account, _) = pda_helper.find_program_address_by_text(path);
+    let mut accounts = vec![
+        AccountMeta::new(*authority, true),
+        AccountMeta::new(page_account, false),
+    ];
+    if let Some(trunks) = trunks {
+        for trunk_no in 0..=trunks {
+            let (trunk_account, _) =
+                pda_helper.find_program_address_by_text_suffix(path, &[trunk_no]);
+            accounts.push(AccountMeta::new(trunk_account, false));
+        }
+    }
+    Instruction {
+        program_id: *w3_program,
+        accounts,
+        data: InstructionData::DeleteContent {
+            path: path.to_string(),
+        }
+        .to_bytes(),
+    }
+}
+
+pub fn publish_instruction(
+    w3_program: &Pubkey,
+    authority: &Pubkey,
+    name: &str,
+    content_program: &Pubkey,
+    default_page: &str,
+) -> Instruction {
+    let (config_account, _) = PdaHelper::new(*w3_program).find_name_address(name);
+    Instruction {
+        program_id: *w3_program,
+        accounts: vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(config_account, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: InstructionData::NameMapping {
+            name: name.to_string(),
+            program: *content_program,
+            default_page: default_page.to_string(),
+        }
+        .to_bytes(),
+    }
+}
+
+/// Writes `body` as a raw page at `path`, it has to fit in one transaction
+/// together with the calling instruction.
+pub fn put_content<'a>(
+    w3_program: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    content_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    path: &str,
+    body: &[u8],
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &put_content_instruction(w3_program.key, authority.key, path, body),
+        &[
+            authority.clone(),
+            content_account.clone(),
+            system_program.clone(),
+            w3_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+/// Deletes the page at `path`, pass every trunk account of a chunked page in
+/// `trunk_accounts`, at most 256.
+pub fn delete_content<'a>(
+    w3_program: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    page_account: &AccountInfo<'a>,
+    trunk_accounts: &[AccountInfo<'a>],
+    path: &str,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let trunks = match trunk_accounts.len() {
+        0 => None,
+        len => Some(u8::try_from(len - 1).map_err(|_| ProgramError::InvalidArgument)?),
+    };
+    let mut account_infos = vec![authority.clone(), page_account.clone()];
+    account_infos.extend_from_slice(trunk_accounts);
+    account_infos.push(w3_program.clone());
+    invoke_signed(
+        &delete_content_instruction(w3_program.key, authority.key, path, trunks),
+        &account_infos,
+        signer_seeds,
+    )
+}
+
+/// Registers `name` so it serves the pages of `content_program`.
+#[allow(clippy::too_many_arguments)]
+pub fn publish<'a>(
+    w3_program: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    config_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    name: &str,
+    content_program: &Pubkey,
+    default_page: &str,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &publish_instruction(
+            w3_program.key,
+            authority.key,
+            name,
+            content_program,
+            default_page,
+        ),
+        &[
+            authority.clone(),
+            config_account.clone(),
+            system_program.clone(),
+            w3_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
@@ -1,5 +1,8 @@
 use {
-    crate::{instruction::InstructionData, process::config, process::process, process::trunk},
+    crate::{
+        instruction::InstructionData, process::blob, process::config, process::delete,
+        process::page, process::process, process::trunk,
+    },
     borsh::BorshDeserialize,
     solana_program::{
         account_info::AccountInfo, declare_id, entrypoint, entrypoint::ProgramResult, msg,
@@ -9,6 +12,7 @@ use {
 
 declare_id!("9pW59BsNCqtQC1xucwTXYS4Qe9qz5AgSy2jajE63odQb");
 
+#[cfg(not(feature = "no-entrypoint"))]
 entrypoint!(process_instruction);
 pub fn process_instruction(
     program_id: &Pubkey,
@@ -37,6 +41,53 @@ pub fn process_instruction(
                 msg!("Put Trunk Content: {:?} trunk_no: {}", path, trunk_no);
                 trunk::put_trunk_content(program_id, accounts, &path, trunk_no, &body)?
             }
+            InstructionData::PutBlob {
+                hash,
+                size,
+                offset,
+                body,
+            } => blob::put_blob(program_id, accounts, &hash, size, offset, &body)?,
+            InstructionData::LinkBlob { path, hash } => {
+                msg!("Link Blob: {:?}", path);
+                blob::link_blob(program_id, accounts, &path, &hash)?
+            }
+            InstructionData::UnlinkBlob { path } => {
+                msg!("Unlink Blob: {:?}", path);
+                blob::unlink_blob(program_id, accounts, &path)?
+            }
+            InstructionData::CloseBlob { hash } => blob::close_blob(program_id, accounts, &hash)?,
+            InstructionData::PutContentBatch { entries } => {
+                msg!("Put Content Batch: {} entries", entries.len());
+                process::put_content_batch(program_id, accounts, &entries)?
+            }
+            InstructionData::DeleteContent { path } => {
+                msg!("Delete Content: {:?}", path);
+                delete::delete_content(program_id, accounts, &path)?
+            }
+            InstructionData::PutAccountPage {
+                path,
+                account,
+                decoder,
+            } => {
+                msg!("Put Account Page: {:?} -> {:?}", path, account);
+                process::put_account_page(program_id, accounts, &path, account, decoder)?
+            }
+            InstructionData::UpdateName {
+                name,
+                program,
+                default_page,
+            } => {
+                msg!("Update Name: {:?}", name);
+                config::update_name(program_id, accounts, &name, program, default_page)?
+            }
+            InstructionData::TransferName { name, owner } => {
+                msg!("Transfer Name: {:?} -> {:?}", name, owner);
+                config::transfer_name(program_id, accounts, &name, owner)?
+            }
+            InstructionData::ClaimPage { path, authority } => {
+                msg!("Claim Page: {:?} -> {:?}", path, authority);
+                page::claim_page(program_id, accounts, &path, authority)?
+            }
         },
         Err(err) => {
             msg!("Error: {:?}", err);
@@ -22,6 +22,52 @@ pub enum InstructionData {
         program: pubkey::Pubkey,
         default_page: String,
     },
+    PutBlob {
+        hash: [u8; 32],
+        size: u32,
+        offset: u32,
+        body: Vec<u8>,
+    },
+    LinkBlob {
+        path: String,
+        hash: [u8; 32],
+    },
+    UnlinkBlob {
+        path: String,
+    },
+    CloseBlob {
+        hash: [u8; 32],
+    },
+    PutContentBatch {
+        entries: Vec<ContentEntry>,
+    },
+    DeleteContent {
+        path: String,
+    },
+    PutAccountPage {
+        path: String,
+        account: pubkey::Pubkey,
+        decoder: AccountDecoder,
+    },
+    UpdateName {
+        name: String,
+        program: pubkey::Pubkey,
+        default_page: String,
+    },
+    TransferName {
+        name: String,
+        owner: pubkey::Pubkey,
+    },
+    ClaimPage {
+        path: String,
+        authority: pubkey::Pubkey,
+    },
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct ContentEntry {
+    pub path: String,
+    pub body: Vec<u8>,
 }
 
 impl InstructionData {
@@ -32,13 +78,64 @@ impl InstructionData {
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub enum PageData {
-    RawData { data: Vec<u8> },
-    TrunkPage { trunks: u8 },
+    RawData {
+        data: Vec<u8>,
+    },
+    TrunkPage {
+        trunks: u8,
+    },
+    BlobPage {
+        hash: [u8; 32],
+    },
+    AccountPage {
+        account: pubkey::Pubkey,
+        decoder: AccountDecoder,
+    },
+}
+
+/// How a reader turns the data of the account behind an `AccountPage` into
+/// the page body.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub enum AccountDecoder {
+    Raw,
+    /// Borsh fields described by `schema`, e.g. `name:string,score:u64`,
+    /// rendered as a JSON object.
+    BorshJson {
+        schema: String,
+    },
+    ByteRange {
+        offset: u32,
+        length: u32,
+    },
+}
+
+/// Fixed size header at the start of a blob account, the blob content
+/// follows it as raw bytes so large blobs never need to be deserialized.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct BlobHeader {
+    pub hash: [u8; 32],
+    pub creator: pubkey::Pubkey,
+    pub ref_count: u64,
+    pub size: u32,
+    pub sealed: bool,
+}
+
+impl BlobHeader {
+    pub const LEN: usize = 32 + 32 + 8 + 4 + 1;
+    /// Largest blob, sealing hashes it whole within the compute budget of a
+    /// transaction.
+    pub const MAX_SIZE: u32 = 1 << 20;
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        borsh::BorshSerialize::try_to_vec(self).unwrap()
+    }
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct NameConfig {
     pub name: String,
+    /// Program whose PDAs hold the pages of this name. Content is always
+    /// derived by path under this program, which may be another w3 deployment.
     pub program: pubkey::Pubkey,
     pub creator: pubkey::Pubkey,
     pub created_at: u64,
@@ -1,3 +1,7 @@
+pub mod cpi;
 pub mod entrypoint;
 pub mod instruction;
 pub mod process;
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,274 @@
+use borsh::BorshDeserialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    hash, msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_instruction,
+    sysvar::{rent::Rent, Sysvar},
+};
+use w3solana::pda_helper::{PdaHelper, BLOB_SEED};
+
+use crate::{
+    instruction::{BlobHeader, PageData},
+    process::{
+        delete::close_account,
+        page::{check_authority, page_bytes, read_page},
+    },
+};
+
+/// Borsh tag of `PageData::BlobPage`.
+const BLOB_PAGE_TAG: u8 = 2;
+
+pub fn is_blob_page(page_account: &AccountInfo) -> bool {
+    page_account
+        .try_borrow_data()
+        .map(|data| data.first() == Some(&BLOB_PAGE_TAG))
+        .unwrap_or(false)
+}
+
+fn read_header(blob_account: &AccountInfo) -> Result<BlobHeader, ProgramError> {
+    let data = blob_account.try_borrow_data()?;
+    if data.len() < BlobHeader::LEN {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(BlobHeader::try_from_slice(&data[..BlobHeader::LEN])?)
+}
+
+fn write_header(blob_account: &AccountInfo, header: &BlobHeader) -> ProgramResult {
+    let mut data = blob_account.try_borrow_mut_data()?;
+    data[..BlobHeader::LEN].copy_from_slice(&header.to_bytes());
+    Ok(())
+}
+
+pub fn put_blob(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    hash: &[u8; 32],
+    size: u32,
+    offset: u32,
+    body: &[u8],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let blob_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let system_program_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let (blob_pda, bump_seed) = PdaHelper::new(*program_id).find_blob_address(hash);
+    assert!(blob_account.key == &blob_pda);
+    if !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if blob_account.data_is_empty() {
+        if size > BlobHeader::MAX_SIZE {
+            msg!("Blob of {} bytes exceeds {}", size, BlobHeader::MAX_SIZE);
+            return Err(ProgramError::InvalidArgument);
+        }
+        let data_size = BlobHeader::LEN + size as usize;
+        let rent_lamports = Rent::get()?.minimum_balance(data_size);
+        invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                &blob_pda,
+                rent_lamports,
+                data_size as u64,
+                program_id,
+            ),
+            &[
+                payer.clone(),
+                blob_account.clone(),
+                system_program_account.clone(),
+            ],
+            &[&[BLOB_SEED.as_bytes(), hash, &[bump_seed]]],
+        )?;
+        write_header(
+            blob_account,
+            &BlobHeader {
+                hash: *hash,
+                creator: *payer.key,
+                ref_count: 0,
+                size,
+                sealed: false,
+            },
+        )?;
+    }
+
+    let header = read_header(blob_account)?;
+    if header.sealed {
+        msg!("Blob already sealed, skip write");
+        return Ok(());
+    }
+    if header.creator != *payer.key {
+        msg!("Blob is written by {:?}", header.creator);
+        return Err(ProgramError::IllegalOwner);
+    }
+    if header.size != size {
+        msg!("Blob size mismatch: {} != {}", header.size, size);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let start = BlobHeader::LEN + offset as usize;
+    let end = start + body.len();
+    if end > BlobHeader::LEN + size as usize {
+        msg!(
+            "Blob write out of range: {}..{}",
+            offset,
+            end - BlobHeader::LEN
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    let mut data = blob_account.try_borrow_mut_data()?;
+    data[start..end].copy_from_slice(body);
+
+    Ok(())
+}
+
+/// Points the page at `path` to a blob and takes a reference on it. A blob is
+/// sealed on its first link once its content matches the hash it is keyed by.
+/// Chunked pages must be deleted first so their trunks are closed.
+pub fn link_blob(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    path: &str,
+    hash: &[u8; 32],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let page_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let blob_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let system_program_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let pda_helper = PdaHelper::new(*program_id);
+    let (page_pda, bump_seed) = pda_helper.find_program_address_by_text(path);
+    assert!(page_account.key == &page_pda);
+    let (blob_pda, _) = pda_helper.find_blob_address(hash);
+    assert!(blob_account.key == &blob_pda);
+    assert!(blob_account.owner == program_id);
+    check_authority(page_account, payer)?;
+
+    let mut header = read_header(blob_account)?;
+    if !header.sealed {
+        let data = blob_account.try_borrow_data()?;
+        if hash::hash(&data[BlobHeader::LEN..]).to_bytes() != *hash {
+            msg!("Blob content does not match its hash");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        drop(data);
+        header.sealed = true;
+    }
+
+    if !page_account.data_is_empty() {
+        match read_page(page_account)?.0 {
+            PageData::BlobPage {
+                hash: previous_hash,
+            } => {
+                if previous_hash == *hash {
+                    msg!("Page already links this blob");
+                    return write_header(blob_account, &header);
+                }
+                let previous_blob_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+                let (previous_blob_pda, _) = pda_helper.find_blob_address(&previous_hash);
+                assert!(previous_blob_account.key == &previous_blob_pda);
+                let mut previous_header = read_header(previous_blob_account)?;
+                previous_header.ref_count = previous_header.ref_count.saturating_sub(1);
+                write_header(previous_blob_account, &previous_header)?;
+            }
+            PageData::TrunkPage { .. } => {
+                msg!("Page has trunks, delete it first");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            PageData::RawData { .. } | PageData::AccountPage { .. } => {}
+        }
+    }
+
+    let page_data = page_bytes(&PageData::BlobPage { hash: *hash }, payer.key)?;
+    let data_size = page_data.len();
+    if page_account.data_is_empty() {
+        let rent_lamports = Rent::get()?.minimum_balance(data_size);
+        let mut seeds = PdaHelper::get_seeds(path);
+        let bump = [bump_seed];
+        seeds.push(&bump);
+        invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                &page_pda,
+                rent_lamports,
+                data_size as u64,
+                program_id,
+            ),
+            &[
+                payer.clone(),
+                page_account.clone(),
+                system_program_account.clone(),
+            ],
+            &[&seeds],
+        )?;
+    } else {
+        let need_rents = Rent::get()?.minimum_balance(data_size);
+        if page_account.lamports() < need_rents {
+            let amount: u64 = need_rents - page_account.lamports();
+            let transfer_ix = system_instruction::transfer(payer.key, page_account.key, amount);
+            invoke(&transfer_ix, &[payer.clone(), page_account.clone()])?;
+        }
+        page_account.realloc(data_size, true)?;
+    }
+    page_account
+        .try_borrow_mut_data()?
+        .copy_from_slice(&page_data);
+
+    header.ref_count += 1;
+    write_header(blob_account, &header)
+}
+
+/// Removes the blob page at `path`, dropping its reference on the blob and
+/// returning the page rent to its authority, which must sign.
+pub fn unlink_blob(program_id: &Pubkey, accounts: &[AccountInfo], path: &str) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let page_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let blob_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let pda_helper = PdaHelper::new(*program_id);
+    let (page_pda, _) = pda_helper.find_program_address_by_text(path);
+    assert!(page_account.key == &page_pda);
+
+    if !is_blob_page(page_account) {
+        msg!("Page is not a blob page");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    check_authority(page_account, payer)?;
+    if let (PageData::BlobPage { hash }, _) = read_page(page_account)? {
+        let (blob_pda, _) = pda_helper.find_blob_address(&hash);
+        assert!(blob_account.key == &blob_pda);
+        let mut header = read_header(blob_account)?;
+        header.ref_count = header.ref_count.saturating_sub(1);
+        write_header(blob_account, &header)?;
+    }
+
+    close_account(page_account, payer)
+}
+
+/// Closes a blob nobody references anymore, only its creator may do so.
+pub fn close_blob(program_id: &Pubkey, accounts: &[AccountInfo], hash: &[u8; 32]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let creator: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let blob_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let (blob_pda, _) = PdaHelper::new(*program_id).find_blob_address(hash);
+    assert!(blob_account.key == &blob_pda);
+
+    let header = read_header(blob_account)?;
+    if !creator.is_signer || header.creator != *creator.key {
+        msg!("Only the blob creator can close it");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if header.ref_count > 0 {
+        msg!("Blob still referenced by {} pages", header.ref_count);
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    close_account(blob_account, creator)
+}
@@ -1,18 +1,21 @@
 use crate::instruction::NameConfig;
+use borsh::BorshDeserialize;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
-    program::invoke_signed,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
     pubkey::{self, Pubkey},
     system_instruction,
     sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
-use w3solana::pda_helper::PdaHelper;
+use w3solana::pda_helper::{PdaHelper, NAME_SEED};
 
 pub fn name_config(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    name: &String,
+    name: &str,
     program: pubkey::Pubkey,
     default_page: String,
 ) -> ProgramResult {
@@ -20,20 +23,26 @@ pub fn name_config(
     let payer: &AccountInfo<'_> = next_account_info(account_info_iter)?;
     let config_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
     let system_program_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
-    let base_seed = ".w3-solana-name";
-
-    let (config_pda, bump_seed) = PdaHelper::new(program_id.clone())
-        .find_program_address(&[base_seed.as_bytes(), name.as_bytes()]);
+    let (config_pda, bump_seed) = PdaHelper::new(*program_id).find_name_address(name);
 
+    assert!(config_account.key == &config_pda);
     assert!(config_account.data_is_empty());
 
+    if program != *program_id {
+        msg!(
+            "Name {:?} resolves to content of program {:?}",
+            name,
+            program
+        );
+    }
+
     let now = Clock::get()?;
 
     let rent: Rent = Rent::get()?;
     let config_data = NameConfig {
-        name: name.clone(),
+        name: name.to_string(),
         program,
-        creator: payer.key.clone(),
+        creator: *payer.key,
         created_at: now.unix_timestamp as u64,
         default_page,
     };
@@ -53,7 +62,7 @@ pub fn name_config(
             config_account.clone(),
             system_program_account.clone(),
         ],
-        &[&[base_seed.as_bytes(), name.as_bytes(), &[bump_seed]]],
+        &[&[NAME_SEED.as_bytes(), name.as_bytes(), &[bump_seed]]],
     )?;
 
     let mut pda_data = config_account.try_borrow_mut_data()?;
@@ -61,3 +70,91 @@ pub fn name_config(
 
     Ok(())
 }
+
+/// Reads the config of `name`, checking `owner` signed and created it.
+fn owned_config(
+    program_id: &Pubkey,
+    owner: &AccountInfo,
+    config_account: &AccountInfo,
+    name: &str,
+) -> Result<NameConfig, ProgramError> {
+    let (config_pda, _) = PdaHelper::new(*program_id).find_name_address(name);
+    assert!(config_account.key == &config_pda);
+
+    if config_account.data_is_empty() {
+        msg!("Name {:?} is not registered", name);
+        return Err(ProgramError::UninitializedAccount);
+    }
+    let config = NameConfig::try_from_slice(&config_account.try_borrow_data()?)?;
+    if !owner.is_signer || config.creator != *owner.key {
+        msg!("Only the owner of {:?} can change it", name);
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(config)
+}
+
+/// Stores `config`, resizing the account and settling its rent with `owner`.
+fn write_config<'a>(
+    owner: &AccountInfo<'a>,
+    config_account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    config: &NameConfig,
+) -> ProgramResult {
+    let data = config.to_bytes();
+    let need_rents = Rent::get()?.minimum_balance(data.len());
+    let lamports = config_account.lamports();
+    if lamports < need_rents {
+        invoke(
+            &system_instruction::transfer(owner.key, config_account.key, need_rents - lamports),
+            &[
+                owner.clone(),
+                config_account.clone(),
+                system_program_account.clone(),
+            ],
+        )?;
+    } else if lamports > need_rents {
+        **owner.try_borrow_mut_lamports()? += lamports - need_rents;
+        **config_account.try_borrow_mut_lamports()? = need_rents;
+    }
+    config_account.realloc(data.len(), false)?;
+    config_account.try_borrow_mut_data()?.copy_from_slice(&data);
+    Ok(())
+}
+
+/// Points a registered name at another program or default page.
+pub fn update_name(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    name: &str,
+    program: pubkey::Pubkey,
+    default_page: String,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let owner: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let config_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let system_program_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let mut config = owned_config(program_id, owner, config_account, name)?;
+    config.program = program;
+    config.default_page = default_page;
+    write_config(owner, config_account, system_program_account, &config)
+}
+
+/// Hands a registered name over to `new_owner`.
+pub fn transfer_name(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    name: &str,
+    new_owner: pubkey::Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let owner: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let config_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let mut config = owned_config(program_id, owner, config_account, name)?;
+    config.creator = new_owner;
+    config_account
+        .try_borrow_mut_data()?
+        .copy_from_slice(&config.to_bytes());
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use w3solana::pda_helper::PdaHelper;
+
+use crate::{
+    instruction::PageData,
+    process::page::{check_authority, read_page},
+};
+
+pub fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    let lamports = account.lamports();
+    **destination.try_borrow_mut_lamports()? += lamports;
+    **account.try_borrow_mut_lamports()? = 0;
+    account.realloc(0, false)?;
+    Ok(())
+}
+
+/// Closes the page at `path` and, for chunked pages, every trunk account
+/// passed after it. Only the authority of the page may, and gets the rent.
+pub fn delete_content(program_id: &Pubkey, accounts: &[AccountInfo], path: &str) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let page_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let pda_helper = PdaHelper::new(*program_id);
+    let (page_pda, _) = pda_helper.find_program_address_by_text(path);
+    assert!(page_account.key == &page_pda);
+    check_authority(page_account, authority)?;
+
+    if page_account.data_is_empty() {
+        msg!("Page {:?} does not exist", path);
+        return Ok(());
+    }
+
+    match read_page(page_account)?.0 {
+        PageData::RawData { .. } | PageData::AccountPage { .. } => {}
+        PageData::TrunkPage { trunks } => {
+            for trunk_no in 0..=trunks {
+                let trunk_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+                let (trunk_pda, _) =
+                    pda_helper.find_program_address_by_text_suffix(path, &[trunk_no]);
+                assert!(trunk_account.key == &trunk_pda);
+                if !trunk_account.data_is_empty() {
+                    close_account(trunk_account, authority)?;
+                }
+            }
+        }
+        PageData::BlobPage { .. } => {
+            msg!("Page links a blob, unlink it instead");
+            return Err(ProgramError::InvalidAccountData);
+        }
+    }
+
+    close_account(page_account, authority)
+}
@@ -1,3 +1,7 @@
+pub mod blob;
 pub mod config;
+pub mod delete;
+pub mod page;
+#[allow(clippy::module_inception)]
 pub mod process;
 pub mod trunk;
@@ -0,0 +1,148 @@
+//! Authority of a page account.
+//!
+//! A page account holds its borsh `PageData` followed by the 32 byte key of
+//! the authority that wrote it, readers decoding the page alone ignore it.
+//! Only that authority may rewrite or delete the page, trunks included, and
+//! gets its rent back. Pages without one were written before authorities were
+//! recorded, they stay read only until the upgrade authority of the program
+//! hands them to an authority with `ClaimPage`.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke,
+    program_error::ProgramError,
+    program_utils::limited_deserialize,
+    pubkey::Pubkey,
+    system_instruction,
+    sysvar::{rent::Rent, Sysvar},
+};
+use w3solana::pda_helper::PdaHelper;
+
+use crate::instruction::PageData;
+
+/// Data of a page account holding `page` written by `authority`.
+pub fn page_bytes(page: &PageData, authority: &Pubkey) -> Result<Vec<u8>, ProgramError> {
+    let mut data = page.try_to_vec()?;
+    data.extend_from_slice(authority.as_ref());
+    Ok(data)
+}
+
+/// The page held by `page_account` and the authority recorded with it.
+pub fn read_page(page_account: &AccountInfo) -> Result<(PageData, Option<Pubkey>), ProgramError> {
+    let data = page_account.try_borrow_data()?;
+    let rest = &mut &data[..];
+    let page = PageData::deserialize(rest)?;
+    let authority = Pubkey::try_from(*rest).ok();
+    Ok((page, authority))
+}
+
+/// Checks `authority` signed and is the authority recorded on the existing
+/// page, if any.
+pub fn check_authority(page_account: &AccountInfo, authority: &AccountInfo) -> ProgramResult {
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if page_account.data_is_empty() {
+        return Ok(());
+    }
+    match read_page(page_account)? {
+        (_, Some(recorded)) if recorded != *authority.key => {
+            msg!("Page belongs to {:?}", recorded);
+            Err(ProgramError::IllegalOwner)
+        }
+        (_, Some(_)) => Ok(()),
+        (_, None) => {
+            msg!("Page has no authority yet, it must be claimed first");
+            Err(ProgramError::IllegalOwner)
+        }
+    }
+}
+
+/// Records `authority` on the page at `path` written before authorities
+/// were, signed by the upgrade authority of the program. Accounts are the
+/// upgrade authority, paying for the larger page, the page, the program data
+/// account and the system program.
+pub fn claim_page(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    path: &str,
+    authority: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let page_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let program_data_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let system_program_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let (page_pda, _) = PdaHelper::new(*program_id).find_program_address_by_text(path);
+    assert!(page_account.key == &page_pda);
+    check_upgrade_authority(program_id, program_data_account, payer)?;
+
+    if page_account.data_is_empty() {
+        msg!("Page {:?} does not exist", path);
+        return Err(ProgramError::UninitializedAccount);
+    }
+    let page = match read_page(page_account)? {
+        (_, Some(recorded)) => {
+            msg!("Page already belongs to {:?}", recorded);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        (page, None) => page,
+    };
+
+    let data = page_bytes(&page, &authority)?;
+    let need_rents = Rent::get()?.minimum_balance(data.len());
+    if page_account.lamports() < need_rents {
+        invoke(
+            &system_instruction::transfer(
+                payer.key,
+                page_account.key,
+                need_rents - page_account.lamports(),
+            ),
+            &[
+                payer.clone(),
+                page_account.clone(),
+                system_program_account.clone(),
+            ],
+        )?;
+    }
+    page_account.realloc(data.len(), false)?;
+    page_account.try_borrow_mut_data()?.copy_from_slice(&data);
+    Ok(())
+}
+
+/// Checks `signer` signed and is the upgrade authority recorded in the
+/// program data account of `program_id`.
+fn check_upgrade_authority(
+    program_id: &Pubkey,
+    program_data_account: &AccountInfo,
+    signer: &AccountInfo,
+) -> ProgramResult {
+    let (program_data, _) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    if program_data_account.key != &program_data
+        || program_data_account.owner != &bpf_loader_upgradeable::id()
+    {
+        msg!("Not the program data account of {:?}", program_id);
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let state = limited_deserialize(
+        &program_data_account.try_borrow_data()?,
+        UpgradeableLoaderState::size_of_programdata_metadata() as u64,
+    )
+    .map_err(|_| ProgramError::InvalidAccountData)?;
+    match state {
+        UpgradeableLoaderState::ProgramData {
+            upgrade_authority_address: Some(upgrade_authority),
+            ..
+        } if signer.is_signer && upgrade_authority == *signer.key => Ok(()),
+        _ => {
+            msg!("Only the upgrade authority of the program claims pages");
+            Err(ProgramError::MissingRequiredSignature)
+        }
+    }
+}
@@ -1,36 +1,142 @@
-use borsh::BorshSerialize;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
+    msg,
     program::{invoke, invoke_signed},
+    program_error::ProgramError,
     pubkey::Pubkey,
     system_instruction,
     sysvar::{rent::Rent, Sysvar},
 };
 use w3solana::pda_helper::PdaHelper;
 
-use crate::instruction::PageData;
+use crate::{
+    instruction::{AccountDecoder, ContentEntry, PageData},
+    process::{
+        blob::is_blob_page,
+        page::{check_authority, page_bytes},
+    },
+};
 
 pub fn put_content(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    path: &String,
-    body: &Vec<u8>,
+    path: &str,
+    body: &[u8],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let content_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let system_program_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    write_content(
+        program_id,
+        payer,
+        content_account,
+        system_program_account,
+        path,
+        body,
+    )
+}
+
+/// Writes several small pages in one instruction, accounts are the payer, the
+/// system program and then one content account per entry in order.
+pub fn put_content_batch(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    entries: &[ContentEntry],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let system_program_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    for entry in entries {
+        let content_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+        msg!("Put Batch Content: {:?}", entry.path);
+        write_content(
+            program_id,
+            payer,
+            content_account,
+            system_program_account,
+            &entry.path,
+            &entry.body,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_content<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    content_account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    path: &str,
+    body: &[u8],
+) -> ProgramResult {
+    let page = PageData::RawData {
+        data: body.to_vec(),
+    };
+    write_page(
+        program_id,
+        payer,
+        content_account,
+        system_program_account,
+        path,
+        &page,
+    )
+}
+
+/// Points the page at `path` to a live account decoded by the reader.
+pub fn put_account_page(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    path: &str,
+    account: Pubkey,
+    decoder: AccountDecoder,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let payer: &AccountInfo<'_> = next_account_info(account_info_iter)?;
     let content_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
     let system_program_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
 
-    let (content_pda, bump_seed) =
-        PdaHelper::new(program_id.clone()).find_program_address_by_text(&path);
+    let page = PageData::AccountPage { account, decoder };
+    write_page(
+        program_id,
+        payer,
+        content_account,
+        system_program_account,
+        path,
+        &page,
+    )
+}
+
+/// Writes `page` to the page account at `path`, recording `payer` as its
+/// authority. An existing page is only rewritten by its authority.
+pub fn write_page<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    content_account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    path: &str,
+    page: &PageData,
+) -> ProgramResult {
+    let (content_pda, bump_seed) = PdaHelper::new(*program_id).find_program_address_by_text(path);
 
     assert!(content_account.key == &content_pda);
-    let raw_page_data = &PageData::RawData { data: body.clone() }.try_to_vec()?;
+    check_authority(content_account, payer)?;
+    if is_blob_page(content_account) {
+        msg!("Page links a blob, unlink it first");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let raw_page_data = &page_bytes(page, payer.key)?;
     let data_size = raw_page_data.len();
     if content_account.data_is_empty() {
         let rent: Rent = Rent::get()?;
         let rent_lamports = rent.minimum_balance(data_size);
+        let mut seeds = PdaHelper::get_seeds(path);
+        let bump = [bump_seed];
+        seeds.push(&bump);
         invoke_signed(
             &system_instruction::create_account(
                 payer.key,
@@ -44,7 +150,7 @@ pub fn put_content(
                 content_account.clone(),
                 system_program_account.clone(),
             ],
-            &[&[path.as_bytes(), &[bump_seed]]],
+            &[&seeds],
         )?;
         let mut pda_data = content_account.try_borrow_mut_data()?;
         pda_data.copy_from_slice(raw_page_data);
@@ -57,7 +163,7 @@ pub fn put_content(
         }
         content_account.realloc(data_size, true)?;
         let mut pda_data = content_account.try_borrow_mut_data()?;
-        pda_data.copy_from_slice(&raw_page_data);
+        pda_data.copy_from_slice(raw_page_data);
     }
 
     Ok(())
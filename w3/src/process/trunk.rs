@@ -1,8 +1,6 @@
-use borsh::BorshSerialize;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
-    msg,
     program::{invoke, invoke_signed},
     pubkey::Pubkey,
     system_instruction,
@@ -10,14 +8,14 @@ use solana_program::{
 };
 use w3solana::pda_helper::PdaHelper;
 
-use crate::instruction::PageData;
+use crate::{instruction::PageData, process::process::write_page};
 
 pub fn put_trunk_content(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    path: &String,
+    path: &str,
     trunk_no: u8,
-    body: &Vec<u8>,
+    body: &[u8],
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let payer: &AccountInfo<'_> = next_account_info(account_info_iter)?;
@@ -25,44 +23,19 @@ pub fn put_trunk_content(
     let trunk_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
     let system_program_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
 
-    let pda_helper = PdaHelper::new(program_id.clone());
+    let pda_helper = PdaHelper::new(*program_id);
 
-    {
-        msg!("do trunk meta_account update !!!");
-        let (meta_pda, bump_seed) = pda_helper.find_program_address_by_text(&path);
-        msg!("meta_pda: {:?}", meta_pda);
-        msg!("meta_account.key: {:?}", meta_account.key);
-        assert!(meta_account.key == &meta_pda);
-
-        let meta_page = &PageData::TrunkPage { trunks: trunk_no };
-        let meta_data = meta_page.try_to_vec()?;
-
-        if meta_account.data_is_empty() {
-            let rent: Rent = Rent::get()?;
-            let data_size = meta_data.len();
-            let rent_lamports = rent.minimum_balance(data_size);
-            invoke_signed(
-                &system_instruction::create_account(
-                    payer.key,
-                    &meta_pda,
-                    rent_lamports,
-                    data_size as u64,
-                    program_id,
-                ),
-                &[
-                    payer.clone(),
-                    meta_account.clone(),
-                    system_program_account.clone(),
-                ],
-                &[&[path.as_bytes(), &[bump_seed]]],
-            )?;
-        }
-        let mut pda_data = meta_account.try_borrow_mut_data()?;
-        pda_data.copy_from_slice(&meta_data);
-    }
+    write_page(
+        program_id,
+        payer,
+        meta_account,
+        system_program_account,
+        path,
+        &PageData::TrunkPage { trunks: trunk_no },
+    )?;
 
     let (content_pda, bump_seed) =
-        pda_helper.find_program_address_by_text_suffix(&path, &[trunk_no]);
+        pda_helper.find_program_address_by_text_suffix(path, &[trunk_no]);
 
     assert!(trunk_account.key == &content_pda);
 
@@ -70,6 +43,11 @@ pub fn put_trunk_content(
         let rent: Rent = Rent::get()?;
         let data_size = body.len();
         let rent_lamports = rent.minimum_balance(data_size);
+        let mut seeds = PdaHelper::get_seeds(path);
+        let suffix = [trunk_no];
+        let bump = [bump_seed];
+        seeds.push(&suffix);
+        seeds.push(&bump);
 
         invoke_signed(
             &system_instruction::create_account(
@@ -84,11 +62,11 @@ pub fn put_trunk_content(
                 trunk_account.clone(),
                 system_program_account.clone(),
             ],
-            &[&[path.as_bytes(), &[trunk_no], &[bump_seed]]],
+            &[&seeds],
         )?;
 
         let mut pda_data = trunk_account.try_borrow_mut_data()?;
-        pda_data.copy_from_slice(&body);
+        pda_data.copy_from_slice(body);
     } else {
         let new_size = body.len();
         let need_rents = Rent::get()?.minimum_balance(new_size);
@@ -100,7 +78,7 @@ pub fn put_trunk_content(
         trunk_account.realloc(new_size, true)?;
 
         let mut pda_data = trunk_account.try_borrow_mut_data()?;
-        pda_data.copy_from_slice(&body);
+        pda_data.copy_from_slice(body);
     }
 
     Ok(())
@@ -0,0 +1,1066 @@
+mod tests {
+    use {
+        crate::{
+            cpi,
+            entrypoint::{process_instruction, ID as W3_ID},
+            instruction::{BlobHeader, ContentEntry, InstructionData, NameConfig, PageData},
+        },
+        borsh::{BorshDeserialize, BorshSerialize},
+        solana_program::{
+            account_info::{next_account_info, AccountInfo},
+            clock::Clock,
+            entrypoint::{deserialize, ProgramResult, MAX_PERMITTED_DATA_INCREASE},
+            hash::hash,
+            instruction::{AccountMeta, Instruction},
+            program_error::ProgramError,
+            program_stubs::{set_syscall_stubs, SyscallStubs},
+            program_utils::limited_deserialize,
+            pubkey::Pubkey,
+            rent::Rent,
+            system_instruction::SystemInstruction,
+            system_program,
+        },
+        std::{
+            collections::HashMap,
+            panic::{catch_unwind, AssertUnwindSafe},
+            sync::{Mutex, Once},
+        },
+        w3solana::pda_helper::PdaHelper,
+    };
+
+    /// An example game program publishing its leaderboard through w3, signing
+    /// with its own PDA authority.
+    mod leaderboard {
+        use super::*;
+
+        pub const AUTHORITY_SEED: &[u8] = b"leaderboard-authority";
+        pub const PAGE_PATH: &str = "/leaderboard.json";
+
+        pub fn process_instruction(
+            program_id: &Pubkey,
+            accounts: &[AccountInfo],
+            instruction_data: &[u8],
+        ) -> ProgramResult {
+            let account_info_iter = &mut accounts.iter();
+            let w3_program = next_account_info(account_info_iter)?;
+            let authority = next_account_info(account_info_iter)?;
+            let content_account = next_account_info(account_info_iter)?;
+            let system_program = next_account_info(account_info_iter)?;
+
+            let (authority_pda, bump_seed) =
+                Pubkey::find_program_address(&[AUTHORITY_SEED], program_id);
+            assert!(authority.key == &authority_pda);
+
+            cpi::put_content(
+                w3_program,
+                authority,
+                content_account,
+                system_program,
+                PAGE_PATH,
+                instruction_data,
+                &[&[AUTHORITY_SEED, &[bump_seed]]],
+            )
+        }
+    }
+
+    type Invocation = (Instruction, Vec<Vec<Vec<u8>>>);
+
+    /// Invocations of programs other than the system program.
+    static INVOCATIONS: Mutex<Vec<Invocation>> = Mutex::new(Vec::new());
+    static LOGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    /// Syscalls of the processor on the host: sysvars hold their defaults,
+    /// the system program instructions it invokes are applied to the
+    /// accounts and every other invocation is only recorded.
+    struct TestStubs;
+
+    impl SyscallStubs for TestStubs {
+        fn sol_log(&self, message: &str) {
+            LOGS.lock().unwrap().push(message.to_string());
+        }
+
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Clock) = Clock::default() };
+            0
+        }
+
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            0
+        }
+
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            if instruction.program_id != system_program::ID {
+                let seeds = signers_seeds
+                    .iter()
+                    .map(|seeds| seeds.iter().map(|seed| seed.to_vec()).collect())
+                    .collect();
+                INVOCATIONS
+                    .lock()
+                    .unwrap()
+                    .push((instruction.clone(), seeds));
+                return Ok(());
+            }
+            let account = |index: usize| {
+                let key = instruction.accounts[index].pubkey;
+                let info = account_infos
+                    .iter()
+                    .find(|info| info.key == &key)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?;
+                // a PDA signs through the seeds of the invoking program
+                let signed = info.is_signer
+                    || signers_seeds
+                        .iter()
+                        .any(|seeds| Pubkey::create_program_address(seeds, &W3_ID) == Ok(key));
+                if instruction.accounts[index].is_signer && !signed {
+                    return Err(ProgramError::MissingRequiredSignature);
+                }
+                Ok(info)
+            };
+            let system_instruction = limited_deserialize(&instruction.data, 1232)
+                .map_err(|_| ProgramError::InvalidInstructionData)?;
+            match system_instruction {
+                SystemInstruction::CreateAccount {
+                    lamports,
+                    space,
+                    owner,
+                } => {
+                    let (from, to) = (account(0)?, account(1)?);
+                    if to.lamports() > 0 || !to.data_is_empty() {
+                        return Err(ProgramError::AccountAlreadyInitialized);
+                    }
+                    **from.try_borrow_mut_lamports()? -= lamports;
+                    **to.try_borrow_mut_lamports()? += lamports;
+                    to.realloc(space as usize, true)?;
+                    to.assign(&owner);
+                }
+                SystemInstruction::Transfer { lamports } => {
+                    let (from, to) = (account(0)?, account(1)?);
+                    **from.try_borrow_mut_lamports()? -= lamports;
+                    **to.try_borrow_mut_lamports()? += lamports;
+                }
+                other => panic!("unexpected system instruction {:?}", other),
+            }
+            Ok(())
+        }
+    }
+
+    fn install_stubs() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            set_syscall_stubs(Box::new(TestStubs));
+        });
+    }
+
+    fn logged(text: &str) -> bool {
+        LOGS.lock().unwrap().iter().any(|line| line.contains(text))
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct TestAccount {
+        lamports: u64,
+        data: Vec<u8>,
+        owner: Pubkey,
+    }
+
+    /// Accounts kept between the instructions of a test. Instructions run
+    /// on accounts serialized as the runtime does, so the processor can
+    /// resize and close them, and leave them untouched when they fail.
+    #[derive(Default)]
+    struct Ledger {
+        accounts: HashMap<Pubkey, TestAccount>,
+    }
+
+    impl Ledger {
+        const FUNDS: u64 = 10_000_000_000;
+
+        /// A system account holding `FUNDS` lamports.
+        fn wallet(&mut self) -> Pubkey {
+            let key = Pubkey::new_unique();
+            self.accounts.insert(
+                key,
+                TestAccount {
+                    lamports: Self::FUNDS,
+                    ..TestAccount::default()
+                },
+            );
+            key
+        }
+
+        fn account(&self, key: &Pubkey) -> TestAccount {
+            self.accounts.get(key).cloned().unwrap_or_default()
+        }
+
+        fn data(&self, key: &Pubkey) -> Vec<u8> {
+            self.account(key).data
+        }
+
+        fn process(&mut self, instruction: &Instruction) -> ProgramResult {
+            install_stubs();
+            let mut input = self.serialize(instruction);
+            let (program_id, accounts, data) =
+                unsafe { deserialize(input.as_mut_ptr() as *mut u8) };
+            let result = process_instruction(program_id, &accounts, data);
+            if result.is_ok() {
+                for info in &accounts {
+                    self.accounts.insert(
+                        *info.key,
+                        TestAccount {
+                            lamports: info.lamports(),
+                            data: info.data.borrow().to_vec(),
+                            owner: *info.owner,
+                        },
+                    );
+                }
+            }
+            result
+        }
+
+        /// `process` catching the panics of failed assertions.
+        fn try_process(&mut self, instruction: &Instruction) -> Result<ProgramResult, ()> {
+            catch_unwind(AssertUnwindSafe(|| self.process(instruction))).map_err(|_| ())
+        }
+
+        /// Input of the program entrypoint, as 8 byte words to keep it aligned.
+        fn serialize(&self, instruction: &Instruction) -> Vec<u64> {
+            let mut input = Vec::new();
+            input.extend_from_slice(&(instruction.accounts.len() as u64).to_le_bytes());
+            for (index, meta) in instruction.accounts.iter().enumerate() {
+                let first = instruction.accounts[..index]
+                    .iter()
+                    .position(|other| other.pubkey == meta.pubkey);
+                if let Some(first) = first {
+                    input.push(first as u8);
+                    input.extend_from_slice(&[0; 7]);
+                    continue;
+                }
+                let account = self.account(&meta.pubkey);
+                let executable = meta.pubkey == system_program::ID || meta.pubkey == W3_ID;
+                input.push(u8::MAX);
+                input.push(meta.is_signer as u8);
+                input.push(meta.is_writable as u8);
+                input.push(executable as u8);
+                input.extend_from_slice(&[0; 4]);
+                input.extend_from_slice(meta.pubkey.as_ref());
+                input.extend_from_slice(account.owner.as_ref());
+                input.extend_from_slice(&account.lamports.to_le_bytes());
+                input.extend_from_slice(&(account.data.len() as u64).to_le_bytes());
+                input.extend_from_slice(&account.data);
+                input.resize(input.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+                input.resize(input.len().next_multiple_of(8), 0);
+                input.extend_from_slice(&0u64.to_le_bytes());
+            }
+            input.extend_from_slice(&(instruction.data.len() as u64).to_le_bytes());
+            input.extend_from_slice(&instruction.data);
+            input.extend_from_slice(instruction.program_id.as_ref());
+            input
+                .chunks(8)
+                .map(|word| {
+                    let mut bytes = [0; 8];
+                    bytes[..word.len()].copy_from_slice(word);
+                    u64::from_le_bytes(bytes)
+                })
+                .collect()
+        }
+    }
+
+    fn name_instruction(
+        payer: &Pubkey,
+        config_account: Pubkey,
+        name: &str,
+        program: Pubkey,
+    ) -> Instruction {
+        Instruction {
+            program_id: W3_ID,
+            accounts: vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: InstructionData::NameMapping {
+                name: name.to_string(),
+                program,
+                default_page: "/index.html".to_string(),
+            }
+            .to_bytes(),
+        }
+    }
+
+    fn blob_instruction(data: InstructionData, accounts: Vec<AccountMeta>) -> Instruction {
+        Instruction {
+            program_id: W3_ID,
+            accounts,
+            data: data.to_bytes(),
+        }
+    }
+
+    /// Writes `content` to its blob in chunks of `chunk_size` bytes.
+    fn put_blob(
+        ledger: &mut Ledger,
+        payer: &Pubkey,
+        content: &[u8],
+        chunk_size: usize,
+    ) -> [u8; 32] {
+        let hash = hash(content).to_bytes();
+        let (blob_account, _) = PdaHelper::new(W3_ID).find_blob_address(&hash);
+        for (idx, body) in content.chunks(chunk_size).enumerate() {
+            let instruction = blob_instruction(
+                InstructionData::PutBlob {
+                    hash,
+                    size: content.len() as u32,
+                    offset: (idx * chunk_size) as u32,
+                    body: body.to_vec(),
+                },
+                vec![
+                    AccountMeta::new(*payer, true),
+                    AccountMeta::new(blob_account, false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                ],
+            );
+            ledger.process(&instruction).unwrap();
+        }
+        hash
+    }
+
+    fn link_instruction(
+        payer: &Pubkey,
+        path: &str,
+        hash: [u8; 32],
+        previous: Option<[u8; 32]>,
+    ) -> Instruction {
+        let pda_helper = PdaHelper::new(W3_ID);
+        let mut accounts = vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(pda_helper.find_program_address_by_text(path).0, false),
+            AccountMeta::new(pda_helper.find_blob_address(&hash).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        if let Some(previous) = previous {
+            accounts.push(AccountMeta::new(
+                pda_helper.find_blob_address(&previous).0,
+                false,
+            ));
+        }
+        blob_instruction(
+            InstructionData::LinkBlob {
+                path: path.to_string(),
+                hash,
+            },
+            accounts,
+        )
+    }
+
+    fn unlink_instruction(payer: &Pubkey, path: &str, hash: [u8; 32]) -> Instruction {
+        let pda_helper = PdaHelper::new(W3_ID);
+        blob_instruction(
+            InstructionData::UnlinkBlob {
+                path: path.to_string(),
+            },
+            vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(pda_helper.find_program_address_by_text(path).0, false),
+                AccountMeta::new(pda_helper.find_blob_address(&hash).0, false),
+            ],
+        )
+    }
+
+    fn close_blob_instruction(creator: &Pubkey, hash: [u8; 32]) -> Instruction {
+        blob_instruction(
+            InstructionData::CloseBlob { hash },
+            vec![
+                AccountMeta::new(*creator, true),
+                AccountMeta::new(PdaHelper::new(W3_ID).find_blob_address(&hash).0, false),
+            ],
+        )
+    }
+
+    fn blob_header(ledger: &Ledger, hash: &[u8; 32]) -> BlobHeader {
+        let (blob_account, _) = PdaHelper::new(W3_ID).find_blob_address(hash);
+        BlobHeader::try_from_slice(&ledger.data(&blob_account)[..BlobHeader::LEN]).unwrap()
+    }
+
+    #[test]
+    pub fn example_program_puts_content() {
+        install_stubs();
+
+        let game_program = Pubkey::new_unique();
+        let (authority, _) =
+            Pubkey::find_program_address(&[leaderboard::AUTHORITY_SEED], &game_program);
+        let (content_account, _) =
+            PdaHelper::new(W3_ID).find_program_address_by_text(leaderboard::PAGE_PATH);
+        let system_program_id = system_program::ID;
+
+        let mut lamports = [0, 1_000_000_000, 0, 0];
+        let mut data: [Vec<u8>; 4] = Default::default();
+        let [w3_lamports, authority_lamports, content_lamports, system_lamports] = &mut lamports;
+        let [w3_data, authority_data, content_data, system_data] = &mut data;
+        let accounts = [
+            AccountInfo::new(
+                &W3_ID,
+                false,
+                false,
+                w3_lamports,
+                w3_data,
+                &system_program_id,
+                true,
+                0,
+            ),
+            AccountInfo::new(
+                &authority,
+                false,
+                true,
+                authority_lamports,
+                authority_data,
+                &system_program_id,
+                false,
+                0,
+            ),
+            AccountInfo::new(
+                &content_account,
+                false,
+                true,
+                content_lamports,
+                content_data,
+                &system_program_id,
+                false,
+                0,
+            ),
+            AccountInfo::new(
+                &system_program_id,
+                false,
+                false,
+                system_lamports,
+                system_data,
+                &system_program_id,
+                true,
+                0,
+            ),
+        ];
+
+        let body = br#"[{"player":"alice","score":42}]"#;
+        leaderboard::process_instruction(&game_program, &accounts, body).unwrap();
+
+        let invocations = INVOCATIONS.lock().unwrap();
+        assert_eq!(invocations.len(), 1);
+        let (instruction, signers_seeds) = &invocations[0];
+        assert_eq!(instruction.program_id, W3_ID);
+        assert_eq!(instruction.accounts[0].pubkey, authority);
+        assert!(instruction.accounts[0].is_signer);
+        assert_eq!(instruction.accounts[1].pubkey, content_account);
+
+        let seeds: Vec<&[u8]> = signers_seeds[0].iter().map(|seed| &seed[..]).collect();
+        assert_eq!(
+            Pubkey::create_program_address(&seeds, &game_program).unwrap(),
+            authority
+        );
+
+        match InstructionData::try_from_slice(&instruction.data).unwrap() {
+            InstructionData::PutContent { path, body: data } => {
+                assert_eq!(path, leaderboard::PAGE_PATH);
+                assert_eq!(data, body);
+            }
+            other => panic!("unexpected instruction {:?}", other),
+        }
+    }
+
+    #[test]
+    pub fn delete_instruction_lists_trunks() {
+        let authority = Pubkey::new_unique();
+        let instruction = cpi::delete_content_instruction(&W3_ID, &authority, "/app.js", Some(2));
+        let pda_helper = PdaHelper::new(W3_ID);
+        assert_eq!(instruction.accounts.len(), 5);
+        assert_eq!(
+            instruction.accounts[1].pubkey,
+            pda_helper.find_program_address_by_text("/app.js").0
+        );
+        assert_eq!(
+            instruction.accounts[4].pubkey,
+            pda_helper
+                .find_program_address_by_text_suffix("/app.js", &[2])
+                .0
+        );
+        let instruction = cpi::delete_content_instruction(&W3_ID, &authority, "/app.js", None);
+        assert_eq!(instruction.accounts.len(), 2);
+    }
+
+    #[test]
+    pub fn only_the_owner_transfers_a_name() {
+        use crate::{instruction::NameConfig, process::config::transfer_name};
+
+        let owner = Pubkey::new_unique();
+        let new_owner = Pubkey::new_unique();
+        let (config_account, _) = PdaHelper::new(W3_ID).find_name_address("w3sol");
+        let config = NameConfig {
+            name: "w3sol".to_string(),
+            program: W3_ID,
+            creator: owner,
+            created_at: 0,
+            default_page: "/index.html".to_string(),
+        };
+
+        let mut lamports = [1_000_000_000, 1_000_000];
+        let mut data = [Vec::new(), config.to_bytes()];
+        let [owner_lamports, config_lamports] = &mut lamports;
+        let [owner_data, config_data] = &mut data;
+        let mut accounts = [
+            AccountInfo::new(
+                &owner,
+                true,
+                true,
+                owner_lamports,
+                owner_data,
+                &system_program::ID,
+                false,
+                0,
+            ),
+            AccountInfo::new(
+                &config_account,
+                false,
+                true,
+                config_lamports,
+                config_data,
+                &W3_ID,
+                false,
+                0,
+            ),
+        ];
+
+        accounts[0].is_signer = false;
+        assert!(transfer_name(&W3_ID, &accounts, "w3sol", new_owner).is_err());
+
+        accounts[0].is_signer = true;
+        transfer_name(&W3_ID, &accounts, "w3sol", new_owner).unwrap();
+        let stored = NameConfig::try_from_slice(&accounts[1].try_borrow_data().unwrap()).unwrap();
+        assert_eq!(stored.creator, new_owner);
+        assert_eq!(stored.default_page, "/index.html");
+
+        // the previous owner lost control
+        assert!(transfer_name(&W3_ID, &accounts, "w3sol", owner).is_err());
+    }
+
+    #[test]
+    pub fn name_config_checks_its_account() {
+        let mut ledger = Ledger::default();
+        let payer = ledger.wallet();
+        let content_program = Pubkey::new_unique();
+        let (config_account, _) = PdaHelper::new(W3_ID).find_name_address("elsewhere");
+
+        // any other account is refused
+        let instruction = name_instruction(&payer, Pubkey::new_unique(), "elsewhere", W3_ID);
+        assert!(ledger.try_process(&instruction).is_err());
+        let (other_name, _) = PdaHelper::new(W3_ID).find_name_address("other");
+        let instruction = name_instruction(&payer, other_name, "elsewhere", W3_ID);
+        assert!(ledger.try_process(&instruction).is_err());
+
+        // a name served by another program is registered with a warning
+        let instruction = name_instruction(&payer, config_account, "elsewhere", content_program);
+        ledger.process(&instruction).unwrap();
+        let config = NameConfig::try_from_slice(&ledger.data(&config_account)).unwrap();
+        assert_eq!(config.program, content_program);
+        assert_eq!(config.creator, payer);
+        assert_eq!(ledger.account(&config_account).owner, W3_ID);
+        assert!(logged(&format!(
+            "Name \"elsewhere\" resolves to content of program {:?}",
+            content_program
+        )));
+
+        let (config_account, _) = PdaHelper::new(W3_ID).find_name_address("here");
+        let instruction = name_instruction(&payer, config_account, "here", W3_ID);
+        ledger.process(&instruction).unwrap();
+        assert!(!logged("Name \"here\" resolves"));
+    }
+
+    #[test]
+    pub fn blobs_are_sealed_and_counted() {
+        let mut ledger = Ledger::default();
+        let payer = ledger.wallet();
+        let stranger = ledger.wallet();
+        let pda_helper = PdaHelper::new(W3_ID);
+        let content = b"shared stylesheet content, written in chunks".repeat(3);
+        let hash = put_blob(&mut ledger, &payer, &content, 50);
+        let (blob_account, _) = pda_helper.find_blob_address(&hash);
+        assert_eq!(ledger.data(&blob_account)[BlobHeader::LEN..], content[..]);
+        assert!(!blob_header(&ledger, &hash).sealed);
+
+        ledger
+            .process(&link_instruction(&payer, "/a.css", hash, None))
+            .unwrap();
+        ledger
+            .process(&link_instruction(&payer, "/b.css", hash, None))
+            .unwrap();
+        let header = blob_header(&ledger, &hash);
+        assert!(header.sealed);
+        assert_eq!(header.ref_count, 2);
+        assert_eq!(header.creator, payer);
+
+        // the page records its authority after the page data
+        let (page_account, _) = pda_helper.find_program_address_by_text("/a.css");
+        let data = ledger.data(&page_account);
+        let page = PageData::deserialize(&mut &data[..]).unwrap();
+        assert!(matches!(page, PageData::BlobPage { hash: linked } if linked == hash));
+        assert_eq!(data[data.len() - 32..], payer.to_bytes());
+
+        // linking the blob again keeps the count
+        ledger
+            .process(&link_instruction(&payer, "/a.css", hash, None))
+            .unwrap();
+        assert_eq!(blob_header(&ledger, &hash).ref_count, 2);
+
+        // a referenced blob stays open
+        assert_eq!(
+            ledger.process(&close_blob_instruction(&payer, hash)),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        // only the page authority unlinks or relinks it
+        assert_eq!(
+            ledger.process(&unlink_instruction(&stranger, "/a.css", hash)),
+            Err(ProgramError::IllegalOwner)
+        );
+        let other = put_blob(&mut ledger, &stranger, b"other", 50);
+        assert_eq!(
+            ledger.process(&link_instruction(&stranger, "/a.css", other, Some(hash))),
+            Err(ProgramError::IllegalOwner)
+        );
+        assert_eq!(blob_header(&ledger, &hash).ref_count, 2);
+
+        let payer_lamports = ledger.account(&payer).lamports;
+        let page_lamports = ledger.account(&page_account).lamports;
+        ledger
+            .process(&unlink_instruction(&payer, "/a.css", hash))
+            .unwrap();
+        assert_eq!(blob_header(&ledger, &hash).ref_count, 1);
+        assert!(ledger.data(&page_account).is_empty());
+        assert_eq!(
+            ledger.account(&payer).lamports,
+            payer_lamports + page_lamports
+        );
+
+        // relinking a page moves its reference
+        ledger
+            .process(&link_instruction(&payer, "/b.css", other, Some(hash)))
+            .unwrap();
+        assert_eq!(blob_header(&ledger, &hash).ref_count, 0);
+        assert_eq!(blob_header(&ledger, &other).ref_count, 1);
+
+        // an unreferenced blob is closed by its creator only
+        assert_eq!(
+            ledger.process(&close_blob_instruction(&stranger, hash)),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        ledger
+            .process(&close_blob_instruction(&payer, hash))
+            .unwrap();
+        assert!(ledger.data(&blob_account).is_empty());
+        assert_eq!(ledger.account(&blob_account).lamports, 0);
+    }
+
+    #[test]
+    pub fn link_refuses_content_not_matching_the_hash() {
+        let mut ledger = Ledger::default();
+        let payer = ledger.wallet();
+        let hash = hash(b"expected").to_bytes();
+        let (blob_account, _) = PdaHelper::new(W3_ID).find_blob_address(&hash);
+        let instruction = blob_instruction(
+            InstructionData::PutBlob {
+                hash,
+                size: 8,
+                offset: 0,
+                body: b"tampered".to_vec(),
+            },
+            vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(blob_account, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        );
+        ledger.process(&instruction).unwrap();
+
+        assert_eq!(
+            ledger.process(&link_instruction(&payer, "/index.html", hash, None)),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert!(logged("Blob content does not match its hash"));
+        let header = blob_header(&ledger, &hash);
+        assert!(!header.sealed);
+        assert_eq!(header.ref_count, 0);
+    }
+
+    #[test]
+    pub fn only_the_creator_writes_an_unsealed_blob() {
+        let mut ledger = Ledger::default();
+        let creator = ledger.wallet();
+        let stranger = ledger.wallet();
+        let content = b"the real content";
+        let hash = hash(content).to_bytes();
+        let (blob_account, _) = PdaHelper::new(W3_ID).find_blob_address(&hash);
+        let write = |payer: &Pubkey, size: u32, body: &[u8]| {
+            blob_instruction(
+                InstructionData::PutBlob {
+                    hash,
+                    size,
+                    offset: 0,
+                    body: body.to_vec(),
+                },
+                vec![
+                    AccountMeta::new(*payer, true),
+                    AccountMeta::new(blob_account, false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                ],
+            )
+        };
+
+        assert_eq!(
+            ledger.process(&write(&creator, BlobHeader::MAX_SIZE + 1, b"")),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert!(ledger.data(&blob_account).is_empty());
+
+        ledger
+            .process(&write(&creator, content.len() as u32, &content[..8]))
+            .unwrap();
+        assert_eq!(
+            ledger.process(&write(&stranger, content.len() as u32, b"garbage!")),
+            Err(ProgramError::IllegalOwner)
+        );
+        assert!(logged(&format!("Blob is written by {:?}", creator)));
+        ledger
+            .process(&write(&creator, content.len() as u32, content))
+            .unwrap();
+        ledger
+            .process(&link_instruction(&creator, "/a.txt", hash, None))
+            .unwrap();
+        assert!(blob_header(&ledger, &hash).sealed);
+
+        // a sealed blob is shared, anyone may write it again as a no-op
+        ledger
+            .process(&write(&stranger, content.len() as u32, b"garbage!"))
+            .unwrap();
+        assert_eq!(ledger.data(&blob_account)[BlobHeader::LEN..], content[..]);
+    }
+
+    #[test]
+    pub fn a_page_switches_from_blob_to_inline_content() {
+        let mut ledger = Ledger::default();
+        let payer = ledger.wallet();
+        let hash = put_blob(&mut ledger, &payer, b"linked content", 50);
+        let (page_account, _) = PdaHelper::new(W3_ID).find_program_address_by_text("/a.txt");
+        ledger
+            .process(&link_instruction(&payer, "/a.txt", hash, None))
+            .unwrap();
+        let put = Instruction {
+            program_id: W3_ID,
+            accounts: vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(page_account, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: InstructionData::PutContent {
+                path: "/a.txt".to_string(),
+                body: b"inline".to_vec(),
+            }
+            .to_bytes(),
+        };
+
+        assert_eq!(ledger.process(&put), Err(ProgramError::InvalidAccountData));
+        ledger
+            .process(&unlink_instruction(&payer, "/a.txt", hash))
+            .unwrap();
+        ledger.process(&put).unwrap();
+        let data = ledger.data(&page_account);
+        assert!(matches!(
+            PageData::deserialize(&mut &data[..]).unwrap(),
+            PageData::RawData { data } if data == b"inline"
+        ));
+        assert_eq!(blob_header(&ledger, &hash).ref_count, 0);
+    }
+
+    #[test]
+    pub fn link_refuses_chunked_pages() {
+        let mut ledger = Ledger::default();
+        let payer = ledger.wallet();
+        let hash = put_blob(&mut ledger, &payer, b"small now", 50);
+        let (page_account, _) = PdaHelper::new(W3_ID).find_program_address_by_text("/app.js");
+        let mut page = PageData::TrunkPage { trunks: 2 }.try_to_vec().unwrap();
+        page.extend_from_slice(payer.as_ref());
+        ledger.accounts.insert(
+            page_account,
+            TestAccount {
+                lamports: Rent::default().minimum_balance(page.len()),
+                data: page.clone(),
+                owner: W3_ID,
+            },
+        );
+
+        assert_eq!(
+            ledger.process(&link_instruction(&payer, "/app.js", hash, None)),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(ledger.data(&page_account), page);
+        assert_eq!(blob_header(&ledger, &hash).ref_count, 0);
+    }
+
+    #[test]
+    pub fn batch_puts_pages_of_long_paths() {
+        let mut ledger = Ledger::default();
+        let payer = ledger.wallet();
+        let pda_helper = PdaHelper::new(W3_ID);
+        let long_path = "/assets/images/a-rather-long-file-name.png";
+        assert!(long_path.len() > 32);
+        let batch = |bodies: [&[u8]; 3]| {
+            let paths = ["/index.html", "/style.css", long_path];
+            let mut accounts = vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ];
+            accounts.extend(paths.iter().map(|path| {
+                AccountMeta::new(pda_helper.find_program_address_by_text(path).0, false)
+            }));
+            let entries = paths
+                .iter()
+                .zip(bodies)
+                .map(|(path, body)| ContentEntry {
+                    path: path.to_string(),
+                    body: body.to_vec(),
+                })
+                .collect();
+            Instruction {
+                program_id: W3_ID,
+                accounts,
+                data: InstructionData::PutContentBatch { entries }.to_bytes(),
+            }
+        };
+        let page = |ledger: &Ledger, path: &str| {
+            let data = ledger.data(&pda_helper.find_program_address_by_text(path).0);
+            match PageData::deserialize(&mut &data[..]).unwrap() {
+                PageData::RawData { data } => data,
+                other => panic!("unexpected page {:?}", other),
+            }
+        };
+
+        ledger
+            .process(&batch([b"<html></html>", b"body {}", b"png"]))
+            .unwrap();
+        assert_eq!(page(&ledger, "/index.html"), b"<html></html>");
+        assert_eq!(page(&ledger, "/style.css"), b"body {}");
+        assert_eq!(page(&ledger, long_path), b"png");
+
+        // existing pages are resized in place
+        ledger
+            .process(&batch([b"<html>new</html>", b"", b"a larger png"]))
+            .unwrap();
+        assert_eq!(page(&ledger, "/index.html"), b"<html>new</html>");
+        assert_eq!(page(&ledger, "/style.css"), b"");
+        assert_eq!(page(&ledger, long_path), b"a larger png");
+    }
+
+    #[test]
+    pub fn trunks_of_long_paths_are_created() {
+        let mut ledger = Ledger::default();
+        let payer = ledger.wallet();
+        let pda_helper = PdaHelper::new(W3_ID);
+        let path = "/assets/scripts/a-rather-long-bundle-name.js";
+        let (meta_account, _) = pda_helper.find_program_address_by_text(path);
+        for (trunk_no, body) in [b"first half", b"secondhalf"].iter().enumerate() {
+            let trunk_no = trunk_no as u8;
+            let (trunk_account, _) =
+                pda_helper.find_program_address_by_text_suffix(path, &[trunk_no]);
+            let instruction = Instruction {
+                program_id: W3_ID,
+                accounts: vec![
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new(meta_account, false),
+                    AccountMeta::new(trunk_account, false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                ],
+                data: InstructionData::PutTrunkContent {
+                    path: path.to_string(),
+                    trunk_no,
+                    body: body.to_vec(),
+                }
+                .to_bytes(),
+            };
+            ledger.process(&instruction).unwrap();
+            assert_eq!(ledger.data(&trunk_account), body.to_vec());
+        }
+        let data = ledger.data(&meta_account);
+        assert!(matches!(
+            PageData::deserialize(&mut &data[..]).unwrap(),
+            PageData::TrunkPage { trunks: 1 }
+        ));
+    }
+
+    #[test]
+    pub fn only_the_page_authority_rewrites_and_deletes() {
+        let mut ledger = Ledger::default();
+        let owner = ledger.wallet();
+        let stranger = ledger.wallet();
+        let pda_helper = PdaHelper::new(W3_ID);
+        let path = "/app.js";
+        let (meta_account, _) = pda_helper.find_program_address_by_text(path);
+        let trunk_accounts: Vec<Pubkey> = (0..2)
+            .map(|trunk_no| {
+                pda_helper
+                    .find_program_address_by_text_suffix(path, &[trunk_no])
+                    .0
+            })
+            .collect();
+        let trunk_instruction = |payer: &Pubkey, trunk_no: u8| Instruction {
+            program_id: W3_ID,
+            accounts: vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(meta_account, false),
+                AccountMeta::new(trunk_accounts[trunk_no as usize], false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: InstructionData::PutTrunkContent {
+                path: path.to_string(),
+                trunk_no,
+                body: b"chunk".to_vec(),
+            }
+            .to_bytes(),
+        };
+        let delete_instruction =
+            |authority: &Pubkey| cpi::delete_content_instruction(&W3_ID, authority, path, Some(1));
+
+        ledger.process(&trunk_instruction(&owner, 0)).unwrap();
+        assert_eq!(
+            ledger.process(&trunk_instruction(&stranger, 1)),
+            Err(ProgramError::IllegalOwner)
+        );
+        assert!(ledger.data(&trunk_accounts[1]).is_empty());
+        ledger.process(&trunk_instruction(&owner, 1)).unwrap();
+
+        // a stranger can neither replace the page nor delete it
+        let put = |payer: &Pubkey| Instruction {
+            program_id: W3_ID,
+            accounts: vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(meta_account, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: InstructionData::PutContent {
+                path: path.to_string(),
+                body: b"defaced".to_vec(),
+            }
+            .to_bytes(),
+        };
+        assert_eq!(
+            ledger.process(&put(&stranger)),
+            Err(ProgramError::IllegalOwner)
+        );
+        assert_eq!(
+            ledger.process(&delete_instruction(&stranger)),
+            Err(ProgramError::IllegalOwner)
+        );
+        assert!(logged(&format!("Page belongs to {:?}", owner)));
+
+        // the authority gets the rent of the page and its trunks back
+        let rent: u64 = [meta_account, trunk_accounts[0], trunk_accounts[1]]
+            .iter()
+            .map(|key| ledger.account(key).lamports)
+            .sum();
+        let owner_lamports = ledger.account(&owner).lamports;
+        ledger.process(&delete_instruction(&owner)).unwrap();
+        assert_eq!(ledger.account(&owner).lamports, owner_lamports + rent);
+        assert!(ledger.data(&meta_account).is_empty());
+        assert!(ledger.data(&trunk_accounts[0]).is_empty());
+    }
+
+    #[test]
+    pub fn legacy_pages_are_claimed_by_the_upgrade_authority() {
+        use solana_program::bpf_loader_upgradeable;
+
+        let mut ledger = Ledger::default();
+        let upgrade_authority = ledger.wallet();
+        let owner = ledger.wallet();
+        let path = "/index.html";
+        let (page_account, _) = PdaHelper::new(W3_ID).find_program_address_by_text(path);
+        let legacy = PageData::RawData {
+            data: b"old".to_vec(),
+        }
+        .try_to_vec()
+        .unwrap();
+        ledger.accounts.insert(
+            page_account,
+            TestAccount {
+                lamports: Rent::default().minimum_balance(legacy.len()),
+                data: legacy.clone(),
+                owner: W3_ID,
+            },
+        );
+        let (program_data, _) =
+            Pubkey::find_program_address(&[W3_ID.as_ref()], &bpf_loader_upgradeable::id());
+        // bincode `UpgradeableLoaderState::ProgramData { slot, upgrade_authority_address }`
+        let mut state = 3u32.to_le_bytes().to_vec();
+        state.extend_from_slice(&0u64.to_le_bytes());
+        state.push(1);
+        state.extend_from_slice(upgrade_authority.as_ref());
+        ledger.accounts.insert(
+            program_data,
+            TestAccount {
+                lamports: 1,
+                data: state,
+                owner: bpf_loader_upgradeable::id(),
+            },
+        );
+        let put = |payer: &Pubkey| Instruction {
+            program_id: W3_ID,
+            accounts: vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(page_account, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: InstructionData::PutContent {
+                path: path.to_string(),
+                body: b"new".to_vec(),
+            }
+            .to_bytes(),
+        };
+        let claim = |signer: &Pubkey| Instruction {
+            program_id: W3_ID,
+            accounts: vec![
+                AccountMeta::new(*signer, true),
+                AccountMeta::new(page_account, false),
+                AccountMeta::new_readonly(program_data, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: InstructionData::ClaimPage {
+                path: path.to_string(),
+                authority: owner,
+            }
+            .to_bytes(),
+        };
+
+        // nobody takes over a page written before authorities were recorded
+        assert_eq!(
+            ledger.process(&put(&owner)),
+            Err(ProgramError::IllegalOwner)
+        );
+        assert!(logged("Page has no authority yet"));
+        assert_eq!(
+            ledger.process(&claim(&owner)),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(ledger.data(&page_account), legacy);
+
+        ledger.process(&claim(&upgrade_authority)).unwrap();
+        let data = ledger.data(&page_account);
+        assert_eq!(data[..legacy.len()], legacy[..]);
+        assert_eq!(data[legacy.len()..], owner.to_bytes());
+        assert_eq!(
+            ledger.account(&page_account).lamports,
+            Rent::default().minimum_balance(data.len())
+        );
+        assert_eq!(
+            ledger.process(&claim(&upgrade_authority)),
+            Err(ProgramError::InvalidAccountData)
+        );
+        ledger.process(&put(&owner)).unwrap();
+    }
+}